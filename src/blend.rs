@@ -0,0 +1,95 @@
+use crate::geometry::Vector3D;
+use crate::canvas_trait::RgbColor;
+
+/** How a drawn 0xAARRGGBB color is combined with the pixel under it */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlendMode {
+    /** Overwrite the pixel, alpha included */
+    Replace,
+    /** Normal alpha compositing, source over destination */
+    Over,
+    /** Colors are summed and clipped */
+    Additive,
+    /** Colors are multiplied, always darkens */
+    Multiply,
+    /** Inverted colors are multiplied, always lightens */
+    Screen,
+}
+
+fn channel(color: u32, nth: u32) -> f64 {
+    ((color >> (8 * nth)) & 0xFF) as f64 / 255.0
+}
+
+/**
+ * Composite straight (non-premultiplied) colors with alphas in [0, 1].
+ * Separable blend modes are mixed in by coverage: both alphas pick the blended color,
+ * where only one of them covers the pixel that color shows through.
+ * `clip` keeps additive results displayable.
+ */
+fn composite(mode: BlendMode, source: [f64; 3], alpha_s: f64, destination: [f64; 3], alpha_d: f64, clip: bool) -> ([f64; 3], f64) {
+    let alpha = alpha_s + alpha_d * (1.0 - alpha_s);
+    let mut result = [0.0; 3];
+    if alpha == 0.0 {
+        return (result, 0.0);
+    }
+    for nth in 0..3 {
+        let (s, d) = (source[nth], destination[nth]);
+        let mixed = match mode {
+            BlendMode::Additive if clip => (s + d).min(1.0),
+            BlendMode::Additive => s + d,
+            BlendMode::Multiply => s * d,
+            BlendMode::Screen => s + d - s * d,
+            _ => s,
+        };
+        let premultiplied = s * alpha_s * (1.0 - alpha_d) + d * alpha_d * (1.0 - alpha_s) + mixed * alpha_s * alpha_d;
+        result[nth] = premultiplied / alpha;
+    }
+    (result, alpha)
+}
+
+/** Composite `source` onto `destination`, both 0xAARRGGBB */
+pub fn blend(mode: BlendMode, source: u32, destination: u32) -> u32 {
+    let (alpha_s, alpha_d) = (channel(source, 3), channel(destination, 3));
+    if mode == BlendMode::Replace || alpha_s == 1.0 && mode == BlendMode::Over {
+        return source;
+    }
+    let unpack = |color: u32| [channel(color, 0), channel(color, 1), channel(color, 2)];
+    let (color, alpha) = composite(mode, unpack(source), alpha_s, unpack(destination), alpha_d, true);
+    let mut result = ((alpha * 255.0).round() as u32) << (8 * 3);
+    for nth in 0..3 {
+        result |= ((color[nth] * 255.0).round().min(255.0) as u32) << (8 * nth);
+    }
+    result
+}
+
+/** Composite in linear light without clipping, for HDR framebuffers */
+pub fn blend_linear(mode: BlendMode, source: RgbColor, destination: RgbColor) -> RgbColor {
+    let (alpha_s, alpha_d) = (source.get_alpha() as f64 / 255.0, destination.get_alpha() as f64 / 255.0);
+    if mode == BlendMode::Replace || alpha_s == 1.0 && mode == BlendMode::Over {
+        return source;
+    }
+    let unpack = |color: RgbColor| {
+        let linear = color.to_linear();
+        [linear.x, linear.y, linear.z]
+    };
+    let (color, alpha) = composite(mode, unpack(source), alpha_s, unpack(destination), alpha_d, false);
+    RgbColor::from_linear(Vector3D {x: color[0], y: color[1], z: color[2]})
+        .with_alpha((alpha * 255.0).round() as u8)
+}
+
+#[test]
+fn blend_modes() {
+    let half_red = 0x80FF0000;
+    let gray = 0xFF808080;
+    assert_eq!(blend(BlendMode::Replace, half_red, gray), half_red);
+    assert_eq!(blend(BlendMode::Over, 0xFF00FF00, gray), 0xFF00FF00);
+    assert_eq!(blend(BlendMode::Over, half_red, gray), 0xFFC04040);
+    assert_eq!(blend(BlendMode::Over, half_red, 0), half_red);
+    assert_eq!(blend(BlendMode::Over, 0, gray), gray);
+    assert_eq!(blend(BlendMode::Additive, 0xFF404040, gray), 0xFFC0C0C0);
+    assert_eq!(blend(BlendMode::Multiply, 0xFF808080, 0xFFFFFF00), 0xFF808000);
+    assert_eq!(blend(BlendMode::Screen, 0xFF000000, gray), gray);
+    assert_eq!(blend(BlendMode::Screen, 0xFFFFFFFF, gray), 0xFFFFFFFF);
+    let bright = RgbColor::from_linear(Vector3D {x: 0.75, y: 0.0, z: 0.0});
+    assert_eq!(blend_linear(BlendMode::Additive, bright, bright).to_linear().x, 1.5);
+}
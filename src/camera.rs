@@ -0,0 +1,83 @@
+use crate::geometry::{Vector3D, Matrix4};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /** Parallel projection framing the same area of the target plane as the perspective one */
+    Orthographic,
+}
+
+#[derive(Clone, Copy)]
+pub struct Camera {
+    pub eye: Vector3D,
+    pub target: Vector3D,
+    pub up: Vector3D,
+    /** Vertical field of view in radians */
+    pub fov: f64,
+    pub near: f64,
+    pub far: f64,
+    pub projection: Projection,
+}
+
+impl Camera {
+    pub fn perspective(eye: Vector3D, target: Vector3D) -> Self {
+        Camera {
+            eye: eye,
+            target: target,
+            up: Vector3D {x: 0.0, y: 1.0, z: 0.0},
+            fov: std::f64::consts::PI / 4.0,
+            near: 0.1,
+            far: 100.0,
+            projection: Projection::Perspective,
+        }
+    }
+    pub fn orthographic(eye: Vector3D, target: Vector3D) -> Self {
+        Camera {
+            projection: Projection::Orthographic,
+            ..Camera::perspective(eye, target)
+        }
+    }
+    pub fn is_perspective(&self) -> bool {
+        self.projection == Projection::Perspective
+    }
+    /** `up` has to point off the view direction, or the view matrix has no sideways axis */
+    pub fn has_valid_up(&self) -> bool {
+        let forward = (self.target - self.eye).normalize();
+        (forward * self.up.normalize()).abs() > 1e-9
+    }
+    pub fn view_matrix(&self) -> Matrix4 {
+        Matrix4::look_at(self.eye, self.target, self.up)
+    }
+    /** `aspect` is the width to height ratio of the image */
+    pub fn projection_matrix(&self, aspect: f64) -> Matrix4 {
+        match self.projection {
+            Projection::Perspective => Matrix4::perspective(self.fov, aspect, self.near, self.far),
+            Projection::Orthographic => {
+                let half_height = (self.eye - self.target).abs() * (self.fov / 2.0).tan();
+                let half_width = half_height * aspect;
+                Matrix4::orthographic(-half_width, half_width, -half_height, half_height, self.near, self.far)
+            }
+        }
+    }
+    /** Direction from the point towards the viewer, both in camera space */
+    pub fn view_direction(&self, point: Vector3D) -> Vector3D {
+        match self.projection {
+            Projection::Perspective => (-point).normalize(),
+            Projection::Orthographic => Vector3D {x: 0.0, y: 0.0, z: 1.0},
+        }
+    }
+}
+
+#[test]
+fn camera_projection_kinds() {
+    let eye = Vector3D {x: 0.0, y: 0.0, z: 4.0};
+    let target = Vector3D {x: 0.0, y: 0.0, z: 0.0};
+    let point = Vector3D {x: 1.0, y: 1.0, z: 0.0};
+    for camera in [Camera::perspective(eye, target), Camera::orthographic(eye, target)].iter() {
+        let transform = camera.projection_matrix(1.0) * camera.view_matrix();
+        let projected = transform.transform_point(point);
+        println!("Projected {} to {}", point, projected);
+        // both projections frame the target plane identically
+        assert!((projected.x - 1.0 / (4.0 * (camera.fov / 2.0).tan())).abs() < 1e-9);
+    }
+}
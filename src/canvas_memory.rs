@@ -0,0 +1,81 @@
+use crate::canvas_trait::{Canvas, RenderOptions};
+use crate::framebuffer::Framebuffer;
+use crate::zbuffer::ZBuffer;
+
+/** Headless canvas, rendered image stays in memory */
+pub struct MemoryCanvas {
+    framebuffer: Framebuffer,
+    zbuffer: ZBuffer,
+    options: RenderOptions,
+    width: u32,
+    height: u32,
+}
+
+impl MemoryCanvas {
+    /** Read-only depth buffer, for off-screen depth passes */
+    pub fn get_depth(&self) -> &ZBuffer {
+        &self.zbuffer
+    }
+}
+
+impl Canvas for MemoryCanvas {
+    fn set(&mut self, x: i32, y: i32, color: u32) -> Result<(), String> {
+        self.framebuffer.set(x, y, color | 0xFF000000)
+    }
+    fn set_rgba(&mut self, x: i32, y: i32, color: u32) -> Result<(), String> {
+        self.framebuffer.blend(x, y, color, self.options.blend_mode)?;
+        Ok(())
+    }
+    fn get(&self, x: i32, y: i32) -> Result<u32, String> {
+        self.framebuffer.get(x, y)
+    }
+    fn get_height(&self) -> u32 {
+        self.height
+    }
+    fn get_width(&self) -> u32 {
+        self.width
+    }
+    fn get_zbuffer(&mut self) -> &mut ZBuffer {
+        &mut self.zbuffer
+    }
+    fn get_options(&mut self) -> &mut RenderOptions {
+        &mut self.options
+    }
+    fn get_framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+    fn get_framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
+    fn new(width: u32, height: u32) -> Self {
+        MemoryCanvas {
+            framebuffer: Framebuffer::new(width, height),
+            zbuffer: ZBuffer::new(width, height),
+            options: RenderOptions::default(),
+            width: width,
+            height: height,
+        }
+    }
+    fn out(&mut self) -> Result<(), String> {
+        self.resolve();
+        Ok(())
+    }
+}
+
+#[test]
+fn readback() {
+    use crate::canvas_trait::{RgbColor, Ergbcolor};
+    use crate::geometry::{Vector3D, Triangle};
+    let mut canvas: MemoryCanvas = Canvas::new(10, 10);
+    let color = RgbColor::new(Ergbcolor::ICEBLUE).value();
+    canvas.triangle_colored(Triangle {
+        p0: Vector3D {x: 0.0, y: 0.0, z: 0.0},
+        p1: Vector3D {x: 10.0, y: 0.0, z: 0.0},
+        p2: Vector3D {x: 0.0, y: 10.0, z: 0.0},
+    }, color).unwrap();
+    assert_eq!(canvas.get(1, 1), Ok(color));
+    assert_eq!(canvas.get(9, 9), Ok(0));
+    assert!(canvas.get(10, 0).is_err());
+    let drawn = canvas.get_framebuffer().pixels().iter().filter(|&&pixel| pixel == color).count();
+    assert_eq!(drawn, 45);
+}
@@ -6,23 +6,40 @@ use sdl2::rect::Point;
 use sdl2::pixels::Color;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use crate::canvas_trait::Canvas;
+use crate::canvas_trait::{Canvas, RenderOptions};
+use crate::zbuffer::ZBuffer;
+use crate::framebuffer::Framebuffer;
+use crate::multisample::Antialiasing;
 
 pub struct SdlCanvas {
     renderer: WindowCanvas,
     sdl_context: Sdl,
+    framebuffer: Framebuffer,
+    zbuffer: ZBuffer,
+    options: RenderOptions,
     width: u32,
     height: u32,
 }
 
+impl SdlCanvas {
+    /** Put the framebuffer pixel to the window, the window has no transparency */
+    fn draw_point(&mut self, x: i32, y: i32, color: u32) -> Result<(), String> {
+        self.renderer.set_draw_color(Color::RGB((color >> (8*2)) as u8, (color >> (8*1)) as u8, color as u8));
+        self.renderer.draw_point(Point::new(x, (self.height-1) as i32 - y))
+    }
+}
+
 impl Canvas for SdlCanvas {
     fn set(&mut self, x: i32, y: i32, color: u32) -> Result<(), String> {
-        self.renderer.set_draw_color(Color::RGB((color >> (8*2)) as u8, (color >> (8*1)) as u8, color as u8));
-        self.renderer.draw_point(Point::new(x, (self.height-1) as i32 - y))?;
-        Ok(())
+        self.framebuffer.set(x, y, color | 0xFF000000)?;
+        self.draw_point(x, y, color)
+    }
+    fn set_rgba(&mut self, x: i32, y: i32, color: u32) -> Result<(), String> {
+        let color = self.framebuffer.blend(x, y, color, self.options.blend_mode)?;
+        self.draw_point(x, y, color)
     }
-    fn get(&self, _x: i32, _y: i32) -> Result<u32, String> {
-        Ok(0)
+    fn get(&self, x: i32, y: i32) -> Result<u32, String> {
+        self.framebuffer.get(x, y)
     }
     fn get_height(&self) -> u32 {
         self.height
@@ -30,6 +47,18 @@ impl Canvas for SdlCanvas {
     fn get_width(&self) -> u32 {
         self.width
     }
+    fn get_zbuffer(&mut self) -> &mut ZBuffer {
+        &mut self.zbuffer
+    }
+    fn get_options(&mut self) -> &mut RenderOptions {
+        &mut self.options
+    }
+    fn get_framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+    fn get_framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
     fn new(width: u32, height: u32) -> Self {
         let sdl_context = sdl2::init().unwrap();
         let video_subsystem = sdl_context.video().unwrap();
@@ -44,6 +73,9 @@ impl Canvas for SdlCanvas {
         SdlCanvas {
             renderer: renderer,
             sdl_context: sdl_context,
+            framebuffer: Framebuffer::new(width, height),
+            zbuffer: ZBuffer::new(width, height),
+            options: RenderOptions::default(),
             width: width,
             height: height,
         }
@@ -51,6 +83,16 @@ impl Canvas for SdlCanvas {
 
     fn out(&mut self) -> Result<(), String> {
         let mut event_pump = self.sdl_context.event_pump()?;
+        if self.framebuffer.get_antialiasing() != Antialiasing::None || self.framebuffer.is_hdr() {
+            // triangles went to the samples or HDR values only, so the window is repainted from the resolved image
+            self.resolve();
+            for y in 0..self.height as i32 {
+                for x in 0..self.width as i32 {
+                    let color = self.framebuffer.get(x, y)?;
+                    self.draw_point(x, y, color)?;
+                }
+            }
+        }
         self.renderer.present();
         'running: loop {
             for event in event_pump.poll_iter() {
@@ -71,20 +113,16 @@ impl Canvas for SdlCanvas {
 fn out_of_image_bounds() {
     use crate::canvas_trait::{RgbColor, Ergbcolor};
     let mut canvas: SdlCanvas = Canvas::new(100, 100);
-    match canvas.line( -100, 0, 50, 50, RgbColor::new(Ergbcolor::WHITE).value()) {
-        Result::Ok(_val) => println!("Line {},{}..{},{} is OK", -100, 0, 50, 50),
-        Result::Err(err) => println!("Line {},{}..{},{} is failed: {}", -100, 0, 50, 50, err),
-    }
-    match canvas.line(0, -100, 50, 50, RgbColor::new(Ergbcolor::WHITE).value()) {
-        Result::Ok(_val) => println!("Line {},{}..{},{} is OK", 0, -100, 50, 50),
-        Result::Err(err) => println!("Line {},{}..{},{} is failed: {}", 0, -100, 50, 50, err),
-    }
-    match canvas.line(0, 0, 500,50, RgbColor::new(Ergbcolor::WHITE).value()) {
-        Result::Ok(_val) => println!("Line {},{}..{},{} is OK", 0, 0, 500,50),
-        Result::Err(err) => println!("Line {},{}..{},{} is failed: {}", 0, 0, 500, 50, err),
-    }
-    match canvas.line(0, 0, 50, 500, RgbColor::new(Ergbcolor::WHITE).value()) {
-        Result::Ok(_val) => println!("Line {},{}..{},{} is OK", 0, 0, 50, 500),
-        Result::Err(err) => println!("Line {},{}..{},{} is failed: {}", 0, 0, 50, 500, err),
-    }
+    let white = RgbColor::new(Ergbcolor::WHITE).value();
+    // lines are clipped to the canvas
+    assert!(canvas.line(-100, 0, 50, 50, white).is_ok());
+    assert!(canvas.line(0, -100, 50, 50, white).is_ok());
+    assert!(canvas.line(0, 0, 500, 50, white).is_ok());
+    assert!(canvas.line(0, 0, 50, 500, white).is_ok());
+    assert_eq!(canvas.get(50, 50).unwrap(), white);
+    // single pixels are not
+    assert!(canvas.set(-1, 0, white).is_err());
+    assert!(canvas.set(0, 100, white).is_err());
+    assert!(canvas.get(100, 0).is_err());
+    assert!(canvas.set(99, 99, white).is_ok());
 }
\ No newline at end of file
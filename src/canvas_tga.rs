@@ -1,25 +1,51 @@
-use image::{ImageBuffer, Rgb};
-use crate::canvas_trait::Canvas;
+use std::path::{Path, PathBuf};
+use image::{ImageBuffer, Rgb, Rgba};
+use crate::canvas_trait::{Canvas, RenderOptions};
+use crate::zbuffer::ZBuffer;
+use crate::framebuffer::Framebuffer;
+use crate::tga::{self, Origin};
+
+/** File format written by `TgaCanvas::out` */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OutputFormat {
+    /** Truevision TGA, optionally run-length encoded */
+    Tga { rle: bool, origin: Origin },
+    Png,
+}
 
 pub struct TgaCanvas {
-    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    framebuffer: Framebuffer,
+    zbuffer: ZBuffer,
+    options: RenderOptions,
+    /** Save the alpha channel, the background stays transparent */
+    alpha: bool,
+    path: PathBuf,
+    format: OutputFormat,
     width: u32,
     height: u32,
 }
 
+impl TgaCanvas {
+    pub fn set_alpha(&mut self, alpha: bool) {
+        self.alpha = alpha;
+    }
+    /** Where and how `out` saves the image, `output.tga` with RLE by default */
+    pub fn set_output(&mut self, path: &Path, format: OutputFormat) {
+        self.path = path.to_path_buf();
+        self.format = format;
+    }
+}
+
 impl Canvas for TgaCanvas {
     fn set(&mut self, x: i32, y: i32, color: u32) -> Result<(), String> {
-        if x as u32 >= self.width || y as u32 >= self.height || x < 0 || y < 0 {
-            return Err("Out of bounds coordinates".to_string());
-        }
-        let rgb_color: Rgb<u8> = Rgb { data: [(color >> (8*2)) as u8,
-                                              (color >> (8*1)) as u8,
-                                              (color as u8)] };
-        self.image.get_pixel_mut(x as u32, ((self.height-1) as i32 - y) as u32).data = rgb_color.data;
+        self.framebuffer.set(x, y, color | 0xFF000000)
+    }
+    fn set_rgba(&mut self, x: i32, y: i32, color: u32) -> Result<(), String> {
+        self.framebuffer.blend(x, y, color, self.options.blend_mode)?;
         Ok(())
     }
-    fn get(&self, _x: i32, _y: i32) -> Result<u32, String> {
-        Ok(0)
+    fn get(&self, x: i32, y: i32) -> Result<u32, String> {
+        self.framebuffer.get(x, y)
     }
     fn get_height(&self) -> u32 {
         self.height
@@ -27,17 +53,44 @@ impl Canvas for TgaCanvas {
     fn get_width(&self) -> u32 {
         self.width
     }
+    fn get_zbuffer(&mut self) -> &mut ZBuffer {
+        &mut self.zbuffer
+    }
+    fn get_options(&mut self) -> &mut RenderOptions {
+        &mut self.options
+    }
+    fn get_framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+    fn get_framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
+    }
     fn new(width: u32, height: u32) -> Self {
-        let img = ImageBuffer::<Rgb<u8>, Vec<u8>>::new(width, height);
         TgaCanvas {
-            image: img,
+            framebuffer: Framebuffer::new(width, height),
+            zbuffer: ZBuffer::new(width, height),
+            options: RenderOptions::default(),
+            alpha: false,
+            path: PathBuf::from("output.tga"),
+            format: OutputFormat::Tga { rle: true, origin: Origin::BottomLeft },
             width: width,
             height: height,
         }
     }
     fn out(&mut self) -> Result<(), String> {
-        self.image.save("output.png").unwrap();
-        Ok(())
+        self.resolve();
+        let saved = match self.format {
+            OutputFormat::Tga { rle, origin } => {
+                return tga::save(&self.path, self.width, self.height, self.framebuffer.pixels(), self.alpha, rle, origin);
+            },
+            OutputFormat::Png if self.alpha => ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(self.width, self.height, self.framebuffer.to_rgba_bytes())
+                .ok_or("Image buffer size mismatch".to_string())?
+                .save(&self.path),
+            OutputFormat::Png => ImageBuffer::<Rgb<u8>, Vec<u8>>::from_raw(self.width, self.height, self.framebuffer.to_rgb_bytes())
+                .ok_or("Image buffer size mismatch".to_string())?
+                .save(&self.path),
+        };
+        saved.map_err(|err| format!("Can't write {}: {}", self.path.display(), err))
     }
 }
 
@@ -45,20 +98,24 @@ impl Canvas for TgaCanvas {
 fn out_of_image_bounds() {
     use crate::canvas_trait::{RgbColor, Ergbcolor};
     let mut canvas: TgaCanvas = Canvas::new(100, 100);
-    match canvas.line( -100, 0, 50, 50, RgbColor::new(Ergbcolor::WHITE).value()) {
-        Result::Ok(_val) => println!("Line {},{}..{},{} is OK", -100, 0, 50, 50),
-        Result::Err(err) => println!("Line {},{}..{},{} is failed: {}", -100, 0, 50, 50, err),
-    }
-    match canvas.line(0, -100, 50, 50, RgbColor::new(Ergbcolor::WHITE).value()) {
-        Result::Ok(_val) => println!("Line {},{}..{},{} is OK", 0, -100, 50, 50),
-        Result::Err(err) => println!("Line {},{}..{},{} is failed: {}", 0, -100, 50, 50, err),
-    }
-    match canvas.line(0, 0, 500,50, RgbColor::new(Ergbcolor::WHITE).value()) {
-        Result::Ok(_val) => println!("Line {},{}..{},{} is OK", 0, 0, 500,50),
-        Result::Err(err) => println!("Line {},{}..{},{} is failed: {}", 0, 0, 500, 50, err),
-    }
-    match canvas.line(0, 0, 50, 500, RgbColor::new(Ergbcolor::WHITE).value()) {
-        Result::Ok(_val) => println!("Line {},{}..{},{} is OK", 0, 0, 50, 500),
-        Result::Err(err) => println!("Line {},{}..{},{} is failed: {}", 0, 0, 50, 500, err),
-    }
-}
\ No newline at end of file
+    let white = RgbColor::new(Ergbcolor::WHITE).value();
+    // lines are clipped to the canvas
+    assert!(canvas.line(-100, 0, 50, 50, white).is_ok());
+    assert!(canvas.line(0, -100, 50, 50, white).is_ok());
+    assert!(canvas.line(0, 0, 500, 50, white).is_ok());
+    assert!(canvas.line(0, 0, 50, 500, white).is_ok());
+    assert_eq!(canvas.get(50, 50).unwrap(), white);
+    // single pixels are not
+    assert!(canvas.set(-1, 0, white).is_err());
+    assert!(canvas.set(0, 100, white).is_err());
+    assert!(canvas.get(100, 0).is_err());
+    assert!(canvas.set(99, 99, white).is_ok());
+}
+#[test]
+fn output_errors() {
+    let mut canvas: TgaCanvas = Canvas::new(4, 4);
+    canvas.set_output(Path::new("no/such/directory/output.tga"), OutputFormat::Tga { rle: false, origin: Origin::TopLeft });
+    assert!(canvas.out().is_err());
+    canvas.set_output(Path::new("no/such/directory/output.png"), OutputFormat::Png);
+    assert!(canvas.out().is_err());
+}
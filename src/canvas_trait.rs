@@ -1,109 +1,460 @@
 use std::boxed::Box;
 use std::ops::Mul;
-use std::mem;
+use std::ops::Add;
 use crate::geometry::*;
 use crate::model_trait::Model;
-
-extern crate rand;
-use rand::Rng;
+use crate::zbuffer::ZBuffer;
+use crate::framebuffer::Framebuffer;
+use crate::multisample::{Antialiasing, MAX_SAMPLES};
+use crate::blend::{self, BlendMode};
+use crate::texture::Texture;
+use crate::material::Material;
+use crate::camera::Camera;
+use crate::clipping;
+use crate::shader::*;
+use crate::light::Light;
+use crate::hdr::ToneMapping;
 
 pub trait Canvas {
+    /** Opaque 0xRRGGBB pixel, the top byte is ignored */
     fn set(&mut self, x: i32, y: i32, color: u32) -> Result<(), String>;
+    /** Composite 0xAARRGGBB color onto the pixel by the `blend_mode` option */
+    fn set_rgba(&mut self, x: i32, y: i32, color: u32) -> Result<(), String>;
     fn get(&self, x: i32, y: i32) -> Result<u32, String>;
     fn new(x: u32, y: u32) -> Self;
     fn out(&mut self) -> Result<(), String>;
     fn get_height(&self) -> u32;
     fn get_width(&self) -> u32;
-    fn render_wire(&mut self, model: Box<Model>, multiplier: f64, offset: Vector3D, color: u32) {
-        for elem in model.triangle_iter() {
-            let triangle = (elem.clone() * multiplier) + offset.clone();
-            self.triangle_wire(triangle, color).unwrap();
+    fn get_zbuffer(&mut self) -> &mut ZBuffer;
+    fn get_options(&mut self) -> &mut RenderOptions;
+    /** Everything drawn so far, for readback without `out` */
+    fn get_framebuffer(&self) -> &Framebuffer;
+    fn get_framebuffer_mut(&mut self) -> &mut Framebuffer;
+    fn clear_zbuffer(&mut self) {
+        self.get_zbuffer().clear();
+        if let Some(samples) = self.get_framebuffer_mut().get_samples_mut() {
+            samples.clear_depth();
+        }
+    }
+    /** Anti-aliasing of filled triangles, applies to everything drawn afterwards */
+    fn set_antialiasing(&mut self, antialiasing: Antialiasing) -> Result<(), String> {
+        self.get_framebuffer_mut().set_antialiasing(antialiasing)
+    }
+    /** Accumulate linear light without clipping, tone mapped by `resolve` */
+    fn set_hdr(&mut self, enabled: bool) -> Result<(), String> {
+        self.get_framebuffer_mut().set_hdr(enabled)
+    }
+    /** Downsample anti-aliased samples or tone map HDR values into the image, backends do it before output */
+    fn resolve(&mut self) {
+        let (tone_mapping, exposure) = (self.get_options().tone_mapping, self.get_options().exposure);
+        self.get_framebuffer_mut().resolve(tone_mapping, exposure);
+    }
+    /** Shaded color, kept above 1 on HDR canvases */
+    fn set_color(&mut self, x: i32, y: i32, color: RgbColor) -> Result<(), String> {
+        if self.get_framebuffer().is_hdr() {
+            let blend_mode = self.get_options().blend_mode;
+            return self.get_framebuffer_mut().blend_color(x, y, color, blend_mode).map(|_| ());
         }
+        self.set_rgba(x, y, color.value())
+    }
+    fn get_viewport(&self) -> Matrix4 {
+        Matrix4::viewport(0.0, 0.0, self.get_width() as f64, self.get_height() as f64)
     }
-    fn render_poly_lightning(&mut self, model: Box<Model>, multiplier: f64, offset: Vector3D, color: RgbColor) {
-        let light_vec = Vector3D {x: 0.0, y: 0.0, z: -1.0};
+    /** Camera space to clip space transform */
+    fn get_projection(&self, camera: &Camera) -> Matrix4 {
+        camera.projection_matrix(self.get_width() as f64 / self.get_height() as f64)
+    }
+    fn render_wire(&mut self, model: Box<Model>, transform: &Matrix4, camera: &Camera, color: u32, antialiased: bool) -> Result<(), String> {
+        let clip_transform = self.get_projection(camera) * camera.view_matrix() * *transform;
         for elem in model.triangle_iter() {
-            let triangle = (elem.clone() * multiplier) + offset.clone();
-            let n = triangle.normal();
-            let intensity = light_vec.scalar(n);
-            if intensity < 0.0 {
-                continue;
+            self.triangle_wire_clipped(&elem.to_clip(&clip_transform), color, antialiased)?;
+        }
+        Ok(())
+    }
+    fn get_uniforms(&self, transform: &Matrix4, camera: &Camera, lights: &[Light]) -> Uniforms {
+        Uniforms::new(transform, camera, &self.get_projection(camera), lights)
+    }
+    fn render_poly_lightning(&mut self, model: Box<Model>, transform: &Matrix4, camera: &Camera, lights: &[Light], color: RgbColor, maps: SurfaceMaps) -> Result<(), String> {
+        let mut shader = FlatShader::new(self.get_uniforms(transform, camera, lights), color, maps);
+        self.draw(&*model, &mut shader)
+    }
+    fn render_gouraud(&mut self, model: Box<Model>, transform: &Matrix4, camera: &Camera, lights: &[Light], color: RgbColor, maps: SurfaceMaps) -> Result<(), String> {
+        let mut shader = GouraudShader::new(self.get_uniforms(transform, camera, lights), color, maps);
+        self.draw(&*model, &mut shader)
+    }
+    fn render_phong(&mut self, model: Box<Model>, transform: &Matrix4, camera: &Camera, lights: &[Light], color: RgbColor, material: &Material, maps: SurfaceMaps) -> Result<(), String> {
+        let mut shader = PhongShader::new(self.get_uniforms(transform, camera, lights), color, *material, maps);
+        self.draw(&*model, &mut shader)
+    }
+    fn render_textured(&mut self, model: Box<Model>, transform: &Matrix4, camera: &Camera, lights: &[Light], texture: &Texture, maps: SurfaceMaps) -> Result<(), String> {
+        let mut shader = TextureShader::new(self.get_uniforms(transform, camera, lights), texture, maps);
+        self.draw(&*model, &mut shader)
+    }
+    fn render_vertex_colors(&mut self, model: Box<Model>, transform: &Matrix4, camera: &Camera, color: RgbColor) -> Result<(), String> {
+        let mut shader = VertexColorShader::new(self.get_uniforms(transform, camera, &[]), color);
+        self.draw(&*model, &mut shader)
+    }
+    /** Point cloud of the model as depth tested squares of `size` pixels, in vertex colors where the model has them */
+    fn render_points(&mut self, model: Box<Model>, transform: &Matrix4, camera: &Camera, color: RgbColor, size: u32) -> Result<(), String> {
+        let clip_transform = self.get_projection(camera) * camera.view_matrix() * *transform;
+        let viewport = self.get_viewport();
+        let offset = (size as i32 - 1) / 2;
+        for (i, &point) in model.points().iter().enumerate() {
+            let clip = clip_transform * Vector4D::from_point(point);
+            if clip.w <= 0.0 || clip.x.abs() > clip.w || clip.y.abs() > clip.w || clip.z.abs() > clip.w {
+                continue; // outside of the view frustum
+            }
+            let screen = viewport.transform_point(clip.to_point());
+            let color = model.point_color(i).unwrap_or(color);
+            let (x0, y0) = (screen.x as i32 - offset, screen.y as i32 - offset);
+            for y in y0..y0 + size as i32 {
+                for x in x0..x0 + size as i32 {
+                    if self.get_zbuffer().test(x, y, screen.z) {
+                        self.get_zbuffer().set(x, y, screen.z);
+                        self.set_color(x, y, color)?;
+                    }
+                }
             }
-            let trg_color = color * intensity;
-            self.triangle_colored(triangle, trg_color.value()).unwrap();
         }
+        Ok(())
     }
-    fn render_poly_rnd_colored(&mut self, model: Box<Model>, multiplier: f64, offset: Vector3D) {
-        let mut rng = rand::thread_rng();
-        for elem in model.triangle_iter() {
-            let triangle = (elem.clone() * multiplier) + offset.clone();
-            let color = rng.gen_range(0x000000, 0xFFFFFF);
-            self.triangle_colored(triangle, color).unwrap();
+    fn render_poly_rnd_colored(&mut self, model: Box<Model>, transform: &Matrix4, camera: &Camera) -> Result<(), String> {
+        let mut shader = RandomColorShader::new(self.get_uniforms(transform, camera, &[]));
+        self.draw(&*model, &mut shader)
+    }
+    /** Run the shader over every face of the model */
+    fn draw<S: Shader>(&mut self, model: &Model, shader: &mut S) -> Result<(), String> {
+        let cull_back_faces = self.get_options().cull_back_faces;
+        for face in 0..model.triangle_iter().len() {
+            let (a, b, c) = (shader.vertex(model, face, 0),
+                             shader.vertex(model, face, 1),
+                             shader.vertex(model, face, 2));
+            let clip = [a.0, b.0, c.0];
+            if cull_back_faces && !is_front_face(&clip) {
+                continue;
+            }
+            let varyings = [a.1, b.1, c.1];
+            self.triangle_clipped(&clip, |_x, _y, bc| shader.fragment(Varying::interpolate(&varyings, bc)))?;
         }
+        Ok(())
     }
+    /** Opaque Bresenham line clipped to the canvas */
     fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u32) -> Result<(), String> {
-        let count = if (y1 - y0).abs() > (x1 - x0).abs() {
-                        (y1 - y0)
-                    } else {
-                        (x1 - x0)
-                    }.abs() as f64 + 1.0;
-        debug!("[{},{}..{},{}] - {} iterations", x0, y0, x1, y1, count);
-        let d_x = (x1 - x0) as f64 / count;
-        let d_y = (y1 - y0) as f64 / count;
-
-        for i in 0..count as u32 {
-            let x = x0 as f64 + i as f64 * d_x;
-            let y = y0 as f64 + i as f64 * d_y;
-            self.set(x as i32, y as i32, color)?;
+        let x_max = self.get_width() as f64 - 1.0;
+        let y_max = self.get_height() as f64 - 1.0;
+        let (x0, y0, x1, y1) = match clipping::clip_line(x0 as f64, y0 as f64, x1 as f64, y1 as f64, x_max, y_max) {
+            Some((x0, y0, x1, y1)) => (x0.round() as i32, y0.round() as i32, x1.round() as i32, y1.round() as i32),
+            None => return Ok(()), // whole line is out of the canvas
+        };
+        debug!("[{},{}..{},{}]", x0, y0, x1, y1);
+        // always walk from the lower end of the major axis, so both directions give the same pixels
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        let (x0, y0, x1, y1) = if steep { (y0, x0, y1, x1) } else { (x0, y0, x1, y1) };
+        let (x0, y0, x1, y1) = if x0 > x1 { (x1, y1, x0, y0) } else { (x0, y0, x1, y1) };
+        let d_x = x1 - x0;
+        let d_y = (y1 - y0).abs();
+        let step_y = if y1 > y0 { 1 } else { -1 };
+        let mut error = 2 * d_y - d_x;
+        let mut y = y0;
+        for x in x0..=x1 {
+            if steep {
+                self.set(y, x, color)?;
+            } else {
+                self.set(x, y, color)?;
+            }
+            if error > 0 {
+                y += step_y;
+                error -= 2 * d_x;
+            }
+            error += 2 * d_y;
         }
         Ok(())
     }
-    fn triangle_wire(&mut self, trgl: Triangle, color: u32) -> Result<(), String> {
-        self.line(trgl.p0.x as i32, trgl.p0.y as i32, trgl.p1.x as i32, trgl.p1.y as i32, color)?;
-        self.line(trgl.p1.x as i32, trgl.p1.y as i32, trgl.p2.x as i32, trgl.p2.y as i32, color)?;
-        self.line(trgl.p2.x as i32, trgl.p2.y as i32, trgl.p0.x as i32, trgl.p0.y as i32, color)?;
+    /** Draw `color` with its alpha scaled by `coverage` in [0,1], points outside the canvas are skipped */
+    fn blend(&mut self, x: i32, y: i32, color: u32, coverage: f64) -> Result<(), String> {
+        if self.get(x, y).is_err() {
+            return Ok(());
+        }
+        let color = RgbColor::from_value(color);
+        let alpha = (color.get_alpha() as f64 * coverage.max(0.0).min(1.0)).round() as u8;
+        self.set_rgba(x, y, color.with_alpha(alpha).value())
+    }
+    /**
+     * Anti-aliased line (Xiaolin Wu), each touched pixel is blended by the part of it the line covers.
+     * Coordinates are continuous, pixel centers are at +0.5.
+     */
+    fn line_aa(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: u32) -> Result<(), String> {
+        let x_max = self.get_width() as f64;
+        let y_max = self.get_height() as f64;
+        let (x0, y0, x1, y1) = match clipping::clip_line(x0, y0, x1, y1, x_max, y_max) {
+            Some((x0, y0, x1, y1)) => (x0 - 0.5, y0 - 0.5, x1 - 0.5, y1 - 0.5),
+            None => return Ok(()),
+        };
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        let (x0, y0, x1, y1) = if steep { (y0, x0, y1, x1) } else { (x0, y0, x1, y1) };
+        let (x0, y0, x1, y1) = if x0 > x1 { (x1, y1, x0, y0) } else { (x0, y0, x1, y1) };
+        let gradient = if x1 - x0 == 0.0 { 1.0 } else { (y1 - y0) / (x1 - x0) };
+        let plot = |canvas: &mut Self, x: i32, y: i32, coverage: f64| {
+            if steep {
+                canvas.blend(y, x, color, coverage)
+            } else {
+                canvas.blend(x, y, color, coverage)
+            }
+        };
+        // endpoints are covered only by the part of the line reaching into their pixel
+        let x_start = x0.round();
+        let y_start = y0 + gradient * (x_start - x0);
+        // y may be just below 0 after the shift, where fract() is negative
+        let gap = 1.0 - (x0 + 0.5).fract();
+        let fraction = y_start - y_start.floor();
+        plot(self, x_start as i32, y_start.floor() as i32, (1.0 - fraction) * gap)?;
+        plot(self, x_start as i32, y_start.floor() as i32 + 1, fraction * gap)?;
+        let x_end = x1.round();
+        let y_end = y1 + gradient * (x_end - x1);
+        let gap = (x1 + 0.5).fract();
+        let fraction = y_end - y_end.floor();
+        plot(self, x_end as i32, y_end.floor() as i32, (1.0 - fraction) * gap)?;
+        plot(self, x_end as i32, y_end.floor() as i32 + 1, fraction * gap)?;
+
+        let mut y = y_start + gradient;
+        for x in x_start as i32 + 1..x_end as i32 {
+            plot(self, x, y.floor() as i32, 1.0 - (y - y.floor()))?;
+            plot(self, x, y.floor() as i32 + 1, y - y.floor())?;
+            y += gradient;
+        }
+        Ok(())
+    }
+    /** Segment between screen points, `antialiased` picks `line_aa` over `line` */
+    fn segment(&mut self, a: &Vector3D, b: &Vector3D, color: u32, antialiased: bool) -> Result<(), String> {
+        if antialiased {
+            self.line_aa(a.x, a.y, b.x, b.y, color)
+        } else {
+            self.line(a.x as i32, a.y as i32, b.x as i32, b.y as i32, color)
+        }
+    }
+    fn triangle_wire(&mut self, trgl: Triangle, color: u32, antialiased: bool) -> Result<(), String> {
+        self.segment(&trgl.p0, &trgl.p1, color, antialiased)?;
+        self.segment(&trgl.p1, &trgl.p2, color, antialiased)?;
+        self.segment(&trgl.p2, &trgl.p0, color, antialiased)?;
+        Ok(())
+    }
+    /** Draw edges of the clip space triangle inside the view frustum */
+    fn triangle_wire_clipped(&mut self, clip: &[Vector4D; 3], color: u32, antialiased: bool) -> Result<(), String> {
+        let viewport = self.get_viewport();
+        for i in 0..3 {
+            if let Some((a, b)) = clipping::clip_segment(&clip[i], &clip[(i + 1) % 3]) {
+                let a = viewport.transform_point(a.to_point());
+                let b = viewport.transform_point(b.to_point());
+                self.segment(&a, &b, color, antialiased)?;
+            }
+        }
         Ok(())
     }
     fn triangle_colored(&mut self, trgl: Triangle, color: u32) -> Result<(), String> {
-        let mut t0 = trgl.p0;
-        let mut t1 = trgl.p1;
-        let mut t2 = trgl.p2;
-
-        if t0.y == t1.y && t0.y == t2.y {
-            return Ok(()); // i dont care about degenerate triangles
-        }
-        // sort the vertices, t0, t1, t2 lower-to-upper (bubblesort yay!)
-        if t0.y > t1.y {
-            mem::swap(&mut t0, &mut t1);
-        }
-        if t0.y > t2.y {
-            mem::swap(&mut t0, &mut t2);
-        }
-        if t1.y > t2.y {
-            mem::swap(&mut t1, &mut t2);
-        }
-        let total_height: usize = (t2.y - t0.y) as usize;
-        self.line(t0.x as i32, t0.y as i32, t1.x as i32, t1.y as i32, color)?;
-        self.line(t1.x as i32, t1.y as i32, t2.x as i32, t2.y as i32, color)?;
-        self.line(t2.x as i32, t2.y as i32, t0.x as i32, t0.y as i32, color)?;
-        for i in 0..total_height as usize {
-            let second_half = i as f64 > t1.y - t0.y || t1.y==t0.y;
-            let segment_height: usize = if second_half  { (t2.y - t1.y) as usize } else { (t1.y - t0.y) as usize };
-            let alpha = i as f64 / total_height as f64;
-            let beta  = (i as f64 - if second_half { t1.y - t0.y } else { 0.0 }) / segment_height as f64; // be careful: with above conditions no division by zero here
-            let mut a = t0 + (t2 - t0) * alpha;
-            let mut b = if second_half { t1 + (t2 - t1) * beta } else { t0 + (t1 - t0) * beta };
-            if a.x > b.x {
-                mem::swap(&mut a, &mut b);
+        self.triangle_rasterize(&trgl, &Vector3D {x: 1.0, y: 1.0, z: 1.0}, |_x, _y, _bc| Some(RgbColor::from_value(color)))
+    }
+    /**
+     * Clip triangle given in clip space against the view frustum and rasterize the visible part.
+     * `fragment` gets barycentric weights relative to the whole triangle.
+     */
+    fn triangle_clipped<F>(&mut self, clip: &[Vector4D; 3], mut fragment: F) -> Result<(), String>
+        where F: FnMut(i32, i32, Vector3D) -> Option<RgbColor> {
+        let polygon = clipping::clip_triangle(clip);
+        if polygon.len() < 3 {
+            return Ok(());
+        }
+        let viewport = self.get_viewport();
+        let screen: Vec<Vector3D> = polygon.iter()
+            .map(|v| viewport.transform_point(v.position.to_point()))
+            .collect();
+        // clipped polygon is convex, so a fan covers it
+        for i in 1..polygon.len() - 1 {
+            let (a, b, c) = (&polygon[0], &polygon[i], &polygon[i + 1]);
+            let trgl = Triangle {p0: screen[0], p1: screen[i], p2: screen[i + 1]};
+            let w = Vector3D {x: a.position.w, y: b.position.w, z: c.position.w};
+            self.triangle_rasterize(&trgl, &w, |x, y, bc| {
+                fragment(x, y, a.bc * bc.x + b.bc * bc.y + c.bc * bc.z)
+            })?;
+        }
+        Ok(())
+    }
+    /**
+     * Fill triangle calling `fragment` with barycentric weights of every covered and visible pixel.
+     * `w` holds clip space w of the vertices, weights are perspective corrected with it if enabled.
+     */
+    fn triangle_rasterize<F>(&mut self, trgl: &Triangle, w: &Vector3D, mut fragment: F) -> Result<(), String>
+        where F: FnMut(i32, i32, Vector3D) -> Option<RgbColor> {
+        let perspective_correct = self.get_options().perspective_correct;
+        let mut p = [snap(&trgl.p0), snap(&trgl.p1), snap(&trgl.p2)];
+        let mut order = [0, 1, 2];
+        let mut area = edge(p[0], p[1], p[2]);
+        if area == 0 {
+            return Ok(()); // degenerate triangle covers nothing
+        }
+        if area < 0 {
+            // keep counter-clockwise order so that the fill rule doesn't depend on winding
+            p.swap(1, 2);
+            order.swap(1, 2);
+            area = -area;
+        }
+        let x_min = (p[0].0.min(p[1].0).min(p[2].0) >> SUBPIXEL_BITS).max(0);
+        let y_min = (p[0].1.min(p[1].1).min(p[2].1) >> SUBPIXEL_BITS).max(0);
+        let x_max = (p[0].0.max(p[1].0).max(p[2].0) >> SUBPIXEL_BITS).min(self.get_width() as i64 - 1);
+        let y_max = (p[0].1.max(p[1].1).max(p[2].1) >> SUBPIXEL_BITS).min(self.get_height() as i64 - 1);
+        let barycentric = |point: (i64, i64)| -> Option<Vector3D> {
+            let e = [edge(p[1], p[2], point), edge(p[2], p[0], point), edge(p[0], p[1], point)];
+            if !covered(e[0], p[1], p[2]) || !covered(e[1], p[2], p[0]) || !covered(e[2], p[0], p[1]) {
+                return None;
             }
-            for j in a.x as usize .. (b.x as usize + 1) {
-                self.set(j as i32, t0.y as i32 + i as i32, color).unwrap();
+            let mut bc = [0.0; 3];
+            for i in 0..3 {
+                bc[order[i]] = e[i] as f64 / area as f64;
+            }
+            Some(Vector3D {x: bc[0], y: bc[1], z: bc[2]})
+        };
+        // screen space depth is affine, so it's interpolated before the correction
+        let depth = |bc: &Vector3D| bc.x * trgl.p0.z + bc.y * trgl.p1.z + bc.z * trgl.p2.z;
+        let correct = |bc: Vector3D| if perspective_correct {
+            let bc = Vector3D {x: bc.x / w.x, y: bc.y / w.y, z: bc.z / w.z};
+            bc / (bc.x + bc.y + bc.z)
+        } else {
+            bc
+        };
+        let (offsets, per_sample): (Vec<(i64, i64)>, bool) = match self.get_framebuffer().get_samples() {
+            Some(samples) => (
+                samples.get_positions().iter()
+                    .map(|&(x, y)| ((x * (1 << SUBPIXEL_BITS) as f64).round() as i64,
+                                    (y * (1 << SUBPIXEL_BITS) as f64).round() as i64))
+                    .collect(),
+                match samples.get_antialiasing() {
+                    Antialiasing::Supersample { .. } => true,
+                    _ => false,
+                }),
+            None => (vec![(1 << (SUBPIXEL_BITS - 1), 1 << (SUBPIXEL_BITS - 1))], false),
+        };
+        let multisampled = offsets.len() > 1;
+        let blend_mode = self.get_options().blend_mode;
+        // samples of the pixel passing coverage and depth tests, with their weights and depth
+        let mut visible = [(0, Vector3D::default(), 0.0); MAX_SAMPLES];
+        for y in y_min..(y_max + 1) {
+            for x in x_min..(x_max + 1) {
+                let corner = (x << SUBPIXEL_BITS, y << SUBPIXEL_BITS);
+                let (x, y) = (x as i32, y as i32);
+                if !multisampled {
+                    let bc = match barycentric((corner.0 + offsets[0].0, corner.1 + offsets[0].1)) {
+                        Some(bc) => bc,
+                        None => continue,
+                    };
+                    let z = depth(&bc);
+                    if !self.get_zbuffer().test(x, y, z) {
+                        continue;
+                    }
+                    if let Some(color) = fragment(x, y, correct(bc)) {
+                        self.get_zbuffer().set(x, y, z);
+                        self.set_color(x, y, color)?;
+                    }
+                    continue;
+                }
+                let mut count = 0;
+                for (i, offset) in offsets.iter().enumerate() {
+                    if let Some(bc) = barycentric((corner.0 + offset.0, corner.1 + offset.1)) {
+                        let z = depth(&bc);
+                        if self.get_framebuffer().get_samples().map_or(false, |samples| samples.test(x, y, i, z)) {
+                            visible[count] = (i, bc, z);
+                            count += 1;
+                        }
+                    }
+                }
+                if count == 0 {
+                    continue;
+                }
+                let visible = &visible[..count];
+                let shared = if per_sample {
+                    None
+                } else {
+                    // shade once at the centroid of visible samples, so the point lies inside the triangle
+                    let centroid = visible.iter().fold(Vector3D::default(), |sum, &(_, bc, _)| sum + bc);
+                    match fragment(x, y, correct(centroid / count as f64)) {
+                        Some(color) => Some(color),
+                        None => continue,
+                    }
+                };
+                for &(i, bc, z) in visible.iter() {
+                    let color = match shared {
+                        Some(color) => color,
+                        None => match fragment(x, y, correct(bc)) {
+                            Some(color) => color,
+                            None => continue,
+                        },
+                    };
+                    if let Some(samples) = self.get_framebuffer_mut().get_samples_mut() {
+                        let under = samples.get(x, y, i);
+                        samples.set(x, y, i, blend::blend(blend_mode, color.value(), under), z);
+                    }
+                }
             }
         }
         Ok(())
     }
 }
 
+pub struct RenderOptions {
+    /** Interpolate attributes linearly in camera space instead of screen space */
+    pub perspective_correct: bool,
+    /** Skip faces turned away from the viewer in `Canvas::draw` */
+    pub cull_back_faces: bool,
+    /** How `Canvas::set_rgba` and shaded pixels combine with the pixels already drawn */
+    pub blend_mode: BlendMode,
+    /** How HDR canvases bring linear light into displayable range on `resolve` */
+    pub tone_mapping: ToneMapping,
+    /** Linear light scale applied before tone mapping */
+    pub exposure: f64,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            perspective_correct: true,
+            cull_back_faces: true,
+            blend_mode: BlendMode::Over,
+            tone_mapping: ToneMapping::Clamp,
+            exposure: 1.0,
+        }
+    }
+}
+
+/**
+ * Counter-clockwise faces are turned to the viewer.
+ * Determinant of homogeneous (x, y, w) is used so that vertices behind the camera are handled too.
+ */
+fn is_front_face(clip: &[Vector4D; 3]) -> bool {
+    let (a, b, c) = (&clip[0], &clip[1], &clip[2]);
+    let det = a.x * (b.y * c.w - b.w * c.y)
+            - a.y * (b.x * c.w - b.w * c.x)
+            + a.w * (b.x * c.y - b.y * c.x);
+    det > 0.0
+}
+
+/** Vertices are snapped to fixed point so that shared edges are evaluated identically */
+const SUBPIXEL_BITS: i64 = 8;
+
+fn snap(v: &Vector3D) -> (i64, i64) {
+    ((v.x * (1 << SUBPIXEL_BITS) as f64).round() as i64,
+     (v.y * (1 << SUBPIXEL_BITS) as f64).round() as i64)
+}
+
+/** Doubled signed area of (a, b, c), positive when c is to the left of a->b */
+fn edge(a: (i64, i64), b: (i64, i64), c: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/** Top-left fill rule: pixels exactly on an edge belong to top and left edges only */
+fn covered(w: i64, a: (i64, i64), b: (i64, i64)) -> bool {
+    let top = a.1 == b.1 && b.0 < a.0;
+    let left = b.1 < a.1;
+    w > 0 || (w == 0 && (top || left))
+}
+
 /** Image Colors */
 #[allow(dead_code)]
 pub enum Ergbcolor {
@@ -119,31 +470,131 @@ pub enum Ergbcolor {
 }
 
 #[derive(Clone, Copy)]
+/**
+ * Color kept in linear light so that shading maths neither clips nor wraps.
+ * 8-bit values on the way in and out are sRGB encoded.
+ */
 pub struct RgbColor {
-    red: u8,
-    green: u8,
-    blue: u8,
+    red: f32,
+    green: f32,
+    blue: f32,
+    /** Opacity, 0xFF is opaque */
+    alpha: u8,
 }
 
 impl RgbColor {
     #[allow(dead_code)]
     pub fn new(color: Ergbcolor) -> Self {
         match color {
-            Ergbcolor::RED =>       RgbColor{red: 0xFF, green: 0x00, blue: 0x00}, // 0xFF0000
-            Ergbcolor::GREEN =>     RgbColor{red: 0x00, green: 0xFF, blue: 0x00}, // 0x00FF00
-            Ergbcolor::BLUE =>      RgbColor{red: 0xFF, green: 0x00, blue: 0xFF}, // 0x0000FF
-            Ergbcolor::ICE =>       RgbColor{red: 0xC3, green: 0xCB, blue: 0xD9}, // 0xC3CBD9
-            Ergbcolor::ICEBLUE =>   RgbColor{red: 0x07, green: 0xF3, blue: 0xE5}, // 0x07F3E5
-            Ergbcolor::WHITE =>     RgbColor{red: 0xFF, green: 0xFF, blue: 0xFF}, // 0xFFFFFF
-            Ergbcolor::GRAY =>      RgbColor{red: 0x80, green: 0x80, blue: 0x80}, // 0x808080
-            Ergbcolor::DARKGRAY =>  RgbColor{red: 0x40, green: 0x40, blue: 0x40}, // 0x404040
-            Ergbcolor::BLACK =>     RgbColor{red: 0x00, green: 0x00, blue: 0x00}, // 0x000000
+            Ergbcolor::RED =>       RgbColor::from_rgb(0xFF, 0x00, 0x00), // 0xFF0000
+            Ergbcolor::GREEN =>     RgbColor::from_rgb(0x00, 0xFF, 0x00), // 0x00FF00
+            Ergbcolor::BLUE =>      RgbColor::from_rgb(0xFF, 0x00, 0xFF), // 0x0000FF
+            Ergbcolor::ICE =>       RgbColor::from_rgb(0xC3, 0xCB, 0xD9), // 0xC3CBD9
+            Ergbcolor::ICEBLUE =>   RgbColor::from_rgb(0x07, 0xF3, 0xE5), // 0x07F3E5
+            Ergbcolor::WHITE =>     RgbColor::from_rgb(0xFF, 0xFF, 0xFF), // 0xFFFFFF
+            Ergbcolor::GRAY =>      RgbColor::from_rgb(0x80, 0x80, 0x80), // 0x808080
+            Ergbcolor::DARKGRAY =>  RgbColor::from_rgb(0x40, 0x40, 0x40), // 0x404040
+            Ergbcolor::BLACK =>     RgbColor::from_rgb(0x00, 0x00, 0x00), // 0x000000
+        }
+    }
+    pub fn from_rgb(red: u8, green: u8, blue: u8) -> Self {
+        RgbColor::from_rgba(red, green, blue, 0xFF)
+    }
+    pub fn from_rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        RgbColor{red: srgb_to_linear(red), green: srgb_to_linear(green), blue: srgb_to_linear(blue), alpha: alpha}
+    }
+    /** Opaque color from linear (red, green, blue), values above 1 are kept */
+    pub fn from_linear(color: Vector3D) -> Self {
+        RgbColor{red: color.x as f32, green: color.y as f32, blue: color.z as f32, alpha: 0xFF}
+    }
+    /** Linear (red, green, blue), e.g. as light color weights */
+    pub fn to_linear(&self) -> Vector3D {
+        Vector3D {
+            x: self.red as f64,
+            y: self.green as f64,
+            z: self.blue as f64,
         }
     }
+    /** Color packed as 0xAARRGGBB */
+    pub fn from_value(value: u32) -> Self {
+        RgbColor::from_rgba((value >> (8*2)) as u8, (value >> (8*1)) as u8, value as u8, (value >> (8*3)) as u8)
+    }
+    pub fn get_alpha(&self) -> u8 {
+        self.alpha
+    }
+    pub fn with_alpha(mut self, alpha: u8) -> Self {
+        self.alpha = alpha;
+        self
+    }
+    /** 0xAARRGGBB, sRGB encoded and clipped to the displayable range */
     pub fn value(&self) -> u32 {
-        let mut result: u32 = self.red as u32 * 256;
-        result = (result + self.green as u32) * 256;
-        (result + self.blue as u32) 
+        let mut result: u32 = self.alpha as u32 * 256;
+        result = (result + linear_to_srgb(self.red) as u32) * 256;
+        result = (result + linear_to_srgb(self.green) as u32) * 256;
+        (result + linear_to_srgb(self.blue) as u32) 
+    }
+}
+
+/** Linear light of every 8 bit sRGB value, texture sampling decodes colors without powf */
+const SRGB_TO_LINEAR: [f32; 256] = [
+    0.00000000, 0.00030353, 0.00060705, 0.00091058, 0.00121411, 0.00151763, 0.00182116, 0.00212469,
+    0.00242822, 0.00273174, 0.00303527, 0.00334654, 0.00367651, 0.00402472, 0.00439144, 0.00477695,
+    0.00518152, 0.00560539, 0.00604883, 0.00651209, 0.00699541, 0.00749903, 0.00802319, 0.00856813,
+    0.00913406, 0.00972122, 0.01032982, 0.01096009, 0.01161225, 0.01228649, 0.01298303, 0.01370208,
+    0.01444384, 0.01520851, 0.01599629, 0.01680738, 0.01764195, 0.01850022, 0.01938236, 0.02028856,
+    0.02121901, 0.02217388, 0.02315337, 0.02415763, 0.02518686, 0.02624122, 0.02732089, 0.02842604,
+    0.02955683, 0.03071344, 0.03189603, 0.03310477, 0.03433981, 0.03560131, 0.03688945, 0.03820437,
+    0.03954624, 0.04091520, 0.04231141, 0.04373503, 0.04518620, 0.04666509, 0.04817182, 0.04970657,
+    0.05126946, 0.05286065, 0.05448028, 0.05612849, 0.05780543, 0.05951124, 0.06124605, 0.06301002,
+    0.06480327, 0.06662594, 0.06847817, 0.07036010, 0.07227185, 0.07421357, 0.07618538, 0.07818742,
+    0.08021982, 0.08228271, 0.08437621, 0.08650046, 0.08865559, 0.09084171, 0.09305896, 0.09530747,
+    0.09758735, 0.09989873, 0.10224173, 0.10461648, 0.10702310, 0.10946171, 0.11193243, 0.11443537,
+    0.11697067, 0.11953843, 0.12213877, 0.12477182, 0.12743768, 0.13013648, 0.13286832, 0.13563333,
+    0.13843162, 0.14126329, 0.14412847, 0.14702727, 0.14995979, 0.15292615, 0.15592646, 0.15896084,
+    0.16202938, 0.16513219, 0.16826940, 0.17144110, 0.17464740, 0.17788842, 0.18116424, 0.18447499,
+    0.18782077, 0.19120168, 0.19461783, 0.19806932, 0.20155625, 0.20507874, 0.20863687, 0.21223076,
+    0.21586050, 0.21952620, 0.22322796, 0.22696587, 0.23074005, 0.23455058, 0.23839757, 0.24228112,
+    0.24620133, 0.25015828, 0.25415209, 0.25818285, 0.26225066, 0.26635560, 0.27049779, 0.27467731,
+    0.27889426, 0.28314874, 0.28744084, 0.29177065, 0.29613827, 0.30054379, 0.30498731, 0.30946892,
+    0.31398871, 0.31854678, 0.32314321, 0.32777810, 0.33245154, 0.33716362, 0.34191442, 0.34670406,
+    0.35153260, 0.35640014, 0.36130678, 0.36625260, 0.37123768, 0.37626212, 0.38132601, 0.38642943,
+    0.39157248, 0.39675523, 0.40197778, 0.40724021, 0.41254261, 0.41788507, 0.42326767, 0.42869050,
+    0.43415364, 0.43965717, 0.44520119, 0.45078578, 0.45641102, 0.46207700, 0.46778380, 0.47353150,
+    0.47932018, 0.48514994, 0.49102085, 0.49693300, 0.50288646, 0.50888132, 0.51491767, 0.52099557,
+    0.52711513, 0.53327640, 0.53947949, 0.54572446, 0.55201140, 0.55834039, 0.56471151, 0.57112483,
+    0.57758044, 0.58407842, 0.59061884, 0.59720179, 0.60382734, 0.61049557, 0.61720656, 0.62396039,
+    0.63075714, 0.63759687, 0.64447968, 0.65140564, 0.65837482, 0.66538730, 0.67244316, 0.67954247,
+    0.68668531, 0.69387176, 0.70110189, 0.70837578, 0.71569350, 0.72305513, 0.73046074, 0.73791041,
+    0.74540421, 0.75294222, 0.76052450, 0.76815115, 0.77582222, 0.78353779, 0.79129794, 0.79910274,
+    0.80695226, 0.81484657, 0.82278575, 0.83076988, 0.83879901, 0.84687323, 0.85499261, 0.86315721,
+    0.87136712, 0.87962240, 0.88792312, 0.89626935, 0.90466117, 0.91309865, 0.92158186, 0.93011086,
+    0.93868573, 0.94730654, 0.95597335, 0.96468625, 0.97344529, 0.98225055, 0.99110210, 1.00000000,
+];
+
+fn srgb_to_linear(value: u8) -> f32 {
+    SRGB_TO_LINEAR[value as usize]
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let c = value.max(0.0).min(1.0);
+    let encoded = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/** Per channel scale, e.g. by light color */
+impl Mul<Vector3D> for RgbColor {
+    type Output = RgbColor;
+    fn mul(self, k: Vector3D) -> Self {
+        RgbColor {
+            red: self.red * k.x as f32,
+            green: self.green * k.y as f32,
+            blue: self.blue * k.z as f32,
+            alpha: self.alpha,
+        }
     }
 }
 
@@ -151,23 +602,242 @@ impl Mul<f64> for RgbColor {
     type Output = RgbColor;
     fn mul(self, k: f64) -> Self {
         RgbColor {
-            red: (self.red as f64 * k) as u8,
-            green: (self.green as f64 * k) as u8,
-            blue: (self.blue as f64 * k) as u8,
+            red: self.red * k as f32,
+            green: self.green * k as f32,
+            blue: self.blue * k as f32,
+            alpha: self.alpha,
+        }
+    }
+}
+
+impl Add<RgbColor> for RgbColor {
+    type Output = RgbColor;
+    fn add(self, color: RgbColor) -> Self {
+        RgbColor {
+            red: self.red + color.red,
+            green: self.green + color.green,
+            blue: self.blue + color.blue,
+            alpha: self.alpha.max(color.alpha),
         }
     }
 }
 
 #[test]
 fn test_rgb() {
-    let rgb: RgbColor = RgbColor {red: 255, green: 255, blue: 255};
-    println!("Rgb: {:06X}", rgb.value());
-    let rgb: RgbColor = RgbColor {red: 255, green: 0, blue: 0};
-    println!("Rgb: {:06X}", rgb.value());
-    let rgb: RgbColor = RgbColor {red: 0, green: 255, blue: 0};
-    println!("Rgb: {:06X}", rgb.value());
-    let rgb: RgbColor = RgbColor {red: 0, green: 0, blue: 255};
-    println!("Rgb: {:06X}", rgb.value());
-    let rgb: RgbColor = RgbColor {red: 16, green: 16, blue: 16};
-    println!("Rgb: {:06X}", rgb.value());
-}
\ No newline at end of file
+    let rgb: RgbColor = RgbColor::from_rgb(255, 255, 255);
+    println!("Rgb: {:08X}", rgb.value());
+    let rgb: RgbColor = RgbColor::from_rgb(255, 0, 0);
+    println!("Rgb: {:08X}", rgb.value());
+    let rgb: RgbColor = RgbColor::from_rgb(0, 255, 0);
+    println!("Rgb: {:08X}", rgb.value());
+    let rgb: RgbColor = RgbColor::from_rgb(0, 0, 255);
+    println!("Rgb: {:08X}", rgb.value());
+    let rgb: RgbColor = RgbColor::from_rgb(16, 16, 16);
+    println!("Rgb: {:08X}", rgb.value());
+    // 8-bit sRGB survives the trip through linear light
+    for value in 0..=255 {
+        assert_eq!(RgbColor::from_rgb(value, 0, 0).value(), 0xFF000000 | (value as u32) << 16);
+    }
+    let gray = RgbColor::new(Ergbcolor::GRAY);
+    assert!((gray.to_linear().x - 0.2158605).abs() < 1e-6);
+    assert_eq!(RgbColor::from_linear(gray.to_linear()).value(), gray.value());
+    // sums aren't clipped until encoding
+    let bright = RgbColor::new(Ergbcolor::WHITE) * 3.0 + gray;
+    assert!(bright.to_linear().x > 3.0);
+    assert_eq!((bright * 0.25).value(), 0xFFE8E8E8);
+}
+#[test]
+fn rasterize_shared_edge() {
+    use crate::canvas_tga::TgaCanvas;
+    let mut canvas: TgaCanvas = Canvas::new(20, 20);
+    let mut hits = vec![0; 20 * 20];
+    let a = Vector3D {x: 2.0, y: 3.0, z: 0.0};
+    let b = Vector3D {x: 17.0, y: 4.5, z: 0.0};
+    let c = Vector3D {x: 15.5, y: 16.0, z: 0.0};
+    let d = Vector3D {x: 3.0, y: 15.0, z: 0.0};
+    for trgl in vec![Triangle {p0: a, p1: b, p2: c}, Triangle {p0: a, p1: d, p2: c}] {
+        canvas.clear_zbuffer();
+        canvas.triangle_rasterize(&trgl, &Vector3D {x: 1.0, y: 1.0, z: 1.0}, |x, y, bc| {
+            assert!((bc.x + bc.y + bc.z - 1.0).abs() < 1e-9);
+            hits[(y * 20 + x) as usize] += 1;
+            None
+        }).unwrap();
+    }
+    assert!(hits.iter().all(|&h| h <= 1));
+    assert!(hits.iter().filter(|&&h| h == 1).count() > 150);
+}
+
+#[test]
+fn perspective_correct_interpolation() {
+    use crate::canvas_tga::TgaCanvas;
+    let mut canvas: TgaCanvas = Canvas::new(20, 20);
+    let trgl = Triangle {
+        p0: Vector3D {x: 0.0, y: 0.0, z: 0.0},
+        p1: Vector3D {x: 20.0, y: 0.0, z: 0.0},
+        p2: Vector3D {x: 0.0, y: 20.0, z: 0.0},
+    };
+    let w = Vector3D {x: 1.0, y: 4.0, z: 1.0};
+    for &enabled in [false, true].iter() {
+        canvas.clear_zbuffer();
+        canvas.get_options().perspective_correct = enabled;
+        canvas.triangle_rasterize(&trgl, &w, |x, y, bc| {
+            if x == 9 && y == 0 {
+                let affine = Vector3D {x: 0.5, y: 0.475, z: 0.025};
+                let expected = if enabled {
+                    let k = affine.x / w.x + affine.y / w.y + affine.z / w.z;
+                    Vector3D {x: affine.x / w.x / k, y: affine.y / w.y / k, z: affine.z / w.z / k}
+                } else {
+                    affine
+                };
+                println!("Perspective correct {}: {} (expected {})", enabled, bc, expected);
+                assert!((bc - expected).abs() < 1e-9);
+            }
+            None
+        }).unwrap();
+    }
+}
+
+#[cfg(test)]
+fn line_pixels(x0: i32, y0: i32, x1: i32, y1: i32) -> Vec<(i32, i32)> {
+    use crate::canvas_memory::MemoryCanvas;
+    let mut canvas: MemoryCanvas = Canvas::new(10, 10);
+    canvas.line(x0, y0, x1, y1, 0xFFFFFFFF).unwrap();
+    let mut pixels = Vec::new();
+    for y in 0..10 {
+        for x in 0..10 {
+            if canvas.get(x, y).unwrap() != 0 {
+                pixels.push((x, y));
+            }
+        }
+    }
+    pixels
+}
+
+#[test]
+fn bresenham_line() {
+    assert_eq!(line_pixels(2, 3, 2, 3), vec![(2, 3)]);
+    assert_eq!(line_pixels(1, 4, 5, 4), vec![(1, 4), (2, 4), (3, 4), (4, 4), (5, 4)]);
+    assert_eq!(line_pixels(7, 5, 7, 2), vec![(7, 2), (7, 3), (7, 4), (7, 5)]);
+    assert_eq!(line_pixels(0, 0, 3, 3), vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+    assert_eq!(line_pixels(0, 0, 6, 2), vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 1), (5, 2), (6, 2)]);
+    // negative slope keeps both endpoints
+    assert_eq!(line_pixels(0, 6, 2, 0), vec![(2, 0), (2, 1), (1, 2), (1, 3), (1, 4), (0, 5), (0, 6)]);
+    // partly outside the canvas
+    assert_eq!(line_pixels(-5, 1, 2, 1), vec![(0, 1), (1, 1), (2, 1)]);
+    for &(x0, y0, x1, y1) in [(0, 0, 9, 4), (1, 8, 7, 0), (3, 2, 4, 9), (9, 9, 0, 5)].iter() {
+        assert_eq!(line_pixels(x0, y0, x1, y1), line_pixels(x1, y1, x0, y0));
+    }
+}
+
+#[test]
+fn antialiased_line() {
+    use crate::canvas_memory::MemoryCanvas;
+    let mut canvas: MemoryCanvas = Canvas::new(10, 10);
+    canvas.get_framebuffer_mut().clear(0xFF000000);
+    // through pixel centers: solid row, nothing leaks into neighbours
+    canvas.line_aa(0.5, 2.5, 9.5, 2.5, 0xFFFFFFFF).unwrap();
+    assert_eq!(canvas.get(4, 2), Ok(0xFFFFFFFF));
+    assert_eq!(canvas.get(4, 3), Ok(0xFF000000));
+    // between two rows: both get half of the color
+    canvas.line_aa(0.5, 6.0, 9.5, 6.0, 0xFFFFFFFF).unwrap();
+    assert_eq!(canvas.get(4, 5), Ok(0xFF808080));
+    assert_eq!(canvas.get(4, 6), Ok(0xFF808080));
+    // blends with what is already drawn
+    canvas.line_aa(4.5, 0.0, 4.5, 10.0, 0xFFFFFFFF).unwrap();
+    assert_eq!(canvas.get(4, 5), Ok(0xFFFFFFFF));
+    canvas.line_aa(0.0, 0.0, 100.0, 100.0, 0xFFFF0000).unwrap();
+    assert_eq!(canvas.get(1, 1), Ok(0xFFFF0000));
+
+    // endpoints on row 0 are plotted there with the same coverage split as the rest of the line
+    let mut canvas: MemoryCanvas = Canvas::new(10, 10);
+    canvas.get_framebuffer_mut().clear(0xFF000000);
+    canvas.line_aa(0.5, 0.2, 9.5, 0.2, 0xFFFFFFFF).unwrap();
+    let (start, middle, end) = (canvas.get(0, 0).unwrap(), canvas.get(4, 0).unwrap(), canvas.get(9, 0).unwrap());
+    assert!(start > 0xFF000000 && start < middle, "{:08X} {:08X}", start, middle);
+    assert_eq!(start, end);
+    assert_eq!(canvas.get(0, 1), Ok(0xFF000000));
+}
+
+#[test]
+fn antialiased_edges() {
+    use crate::canvas_memory::MemoryCanvas;
+    use crate::multisample::Filter;
+    let trgl = Triangle {
+        p0: Vector3D {x: 0.0, y: 0.0, z: 0.0},
+        p1: Vector3D {x: 10.0, y: 0.0, z: 0.0},
+        p2: Vector3D {x: 0.0, y: 10.0, z: 0.0},
+    };
+    for &antialiasing in [Antialiasing::Supersample { factor: 4, filter: Filter::Box },
+                          Antialiasing::Multisample { samples: 4 }].iter() {
+        let mut canvas: MemoryCanvas = Canvas::new(10, 10);
+        canvas.set_antialiasing(antialiasing).unwrap();
+        canvas.triangle_colored(trgl.clone(), 0xFFFFFFFF).unwrap();
+        canvas.resolve();
+        // inside is solid, pixels cut by the hypotenuse are partly transparent
+        assert_eq!(canvas.get(1, 1), Ok(0xFFFFFFFF));
+        let edge = canvas.get(4, 5).unwrap() >> (8*3);
+        assert!(edge > 0 && edge < 0xFF, "{:?}: {:X}", antialiasing, edge);
+        assert_eq!(canvas.get(8, 8), Ok(0));
+        assert_eq!(canvas.get_framebuffer().pixels()[5 * 10 + 4], canvas.get(4, 4).unwrap());
+    }
+    // tent filter leaks into the neighbours of the covered pixels
+    let mut canvas: MemoryCanvas = Canvas::new(10, 10);
+    canvas.set_antialiasing(Antialiasing::Supersample { factor: 2, filter: Filter::Tent }).unwrap();
+    canvas.triangle_colored(trgl, 0xFFFFFFFF).unwrap();
+    assert!(canvas.get(6, 4).unwrap() > 0);
+}
+
+#[test]
+fn blended_set() {
+    use crate::canvas_memory::MemoryCanvas;
+    let mut canvas: MemoryCanvas = Canvas::new(4, 4);
+    canvas.set_rgba(0, 0, 0xFF808080).unwrap();
+    canvas.set_rgba(0, 0, 0x80FF0000).unwrap();
+    assert_eq!(canvas.get(0, 0), Ok(0xFFC04040));
+    canvas.get_options().blend_mode = BlendMode::Additive;
+    canvas.set_rgba(0, 0, 0xFF404040).unwrap();
+    assert_eq!(canvas.get(0, 0), Ok(0xFFFF8080));
+    // translucent color over the transparent background stays translucent
+    canvas.get_options().blend_mode = BlendMode::Over;
+    canvas.set_rgba(1, 1, 0x80FF0000).unwrap();
+    assert_eq!(canvas.get(1, 1), Ok(0x80FF0000));
+    // plain set stays an opaque write whatever the top byte and blend mode
+    canvas.set(0, 0, 0xFF0000).unwrap();
+    assert_eq!(canvas.get(0, 0), Ok(0xFFFF0000));
+    canvas.get_options().blend_mode = BlendMode::Additive;
+    canvas.set(1, 1, 0x0000FF).unwrap();
+    assert_eq!(canvas.get(1, 1), Ok(0xFF0000FF));
+}
+
+#[test]
+fn srgb_table() {
+    for value in 0..=255u8 {
+        let c = value as f64 / 255.0;
+        let linear = if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+        assert!((srgb_to_linear(value) as f64 - linear).abs() < 1e-6, "sRGB {}", value);
+        assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+    }
+}
+
+#[test]
+fn hdr_canvas() {
+    use crate::canvas_memory::MemoryCanvas;
+    let mut canvas: MemoryCanvas = Canvas::new(4, 4);
+    canvas.set_hdr(true).unwrap();
+    assert!(canvas.set_antialiasing(Antialiasing::Multisample {samples: 4}).is_err());
+    let light = RgbColor::from_linear(Vector3D {x: 1.5, y: 0.75, z: 0.0});
+    canvas.get_options().blend_mode = BlendMode::Additive;
+    canvas.set_color(0, 0, light).unwrap();
+    canvas.set_color(0, 0, light).unwrap();
+    canvas.set_color(1, 0, light).unwrap();
+    // clipped preview until resolved
+    assert_eq!(canvas.get(0, 0).unwrap() & 0xFF0000, 0xFF0000);
+    assert_eq!(canvas.get(1, 0).unwrap() & 0xFF0000, 0xFF0000);
+    canvas.get_options().tone_mapping = ToneMapping::Reinhard;
+    canvas.resolve();
+    let (twice, once) = (canvas.get(0, 0).unwrap(), canvas.get(1, 0).unwrap());
+    assert!(twice > once);
+    // 1.5 of linear green maps to 0.6, which is 0xCB in sRGB
+    assert_eq!(twice & 0x00FF00, 0xCB00);
+    assert_eq!(canvas.get(2, 2), Ok(0));
+}
@@ -0,0 +1,323 @@
+use std::path::{Path, PathBuf};
+
+use crate::canvas_trait::{RgbColor, Ergbcolor};
+use crate::canvas_tga::OutputFormat;
+use crate::tga::Origin;
+use crate::geometry::Vector3D;
+use crate::camera::{Camera, Projection};
+use crate::hdr::ToneMapping;
+
+pub const USAGE: &str = "Usage: rust-render [OPTIONS] [MODEL]...
+       rust-render --scene SCENE.json
+
+Renders OBJ, STL or PLY models side by side, files without faces as points, obj/african_head.obj when none is given.
+
+Options:
+      --scene PATH        render a JSON scene file, other options don't apply
+  -o, --output PATH       save the image to PATH (.tga or .png), implies --backend file
+  -b, --backend KIND      window or file [default: window]
+  -s, --size WxH          image resolution [default: 1600x800]
+  -m, --mode MODE         wire, flat, random, lit or vertex_colors [default: lit]
+  -c, --color COLOR       model color, a name or hex RRGGBB[AA] [default: white]
+      --background COLOR  clear the image with COLOR first
+  -t, --texture PATH      diffuse texture of lit models
+  -n, --normal-map PATH   tangent space normal map of lit models, e.g. obj/bumps_nm.tga
+      --eye X,Y,Z         camera position [default: 0,0,3]
+      --target X,Y,Z      point the camera looks at [default: 0,0,0]
+      --fov DEGREES       vertical field of view [default: 45]
+      --orthographic      parallel projection instead of perspective
+      --scale K           model size [default: 0.9]
+      --light X,Y,Z       direction of the directional light [default: 0,0,-1]
+      --tone-mapping OP   clamp, reinhard, aces or exposure, brings HDR light into the image [default: clamp]
+      --exposure K        light scale before tone mapping [default: 1]
+  -h, --help              print this help";
+
+const DEFAULT_MODEL: &str = "obj/african_head.obj";
+const DEFAULT_OUTPUT: &str = "output.tga";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Backend {
+    /** SDL window, shown until closed */
+    Window,
+    /** Image file written by `TgaCanvas` */
+    File,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderMode {
+    Wire,
+    Flat,
+    Random,
+    /** Gouraud shading, textured when a texture is given */
+    Lit,
+    /** Unlit colors of the model vertices */
+    VertexColors,
+}
+
+/** Everything the binary renders, parsed from command line arguments */
+pub struct Options {
+    /** Scene file describing everything instead of the other options */
+    pub scene: Option<PathBuf>,
+    pub models: Vec<PathBuf>,
+    pub backend: Backend,
+    pub output: PathBuf,
+    pub format: OutputFormat,
+    pub width: u32,
+    pub height: u32,
+    pub mode: RenderMode,
+    pub color: RgbColor,
+    pub background: Option<RgbColor>,
+    pub texture: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+    pub camera: Camera,
+    pub scale: f64,
+    pub light: Vector3D,
+    pub tone_mapping: ToneMapping,
+    pub exposure: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scene: None,
+            models: Vec::new(),
+            backend: Backend::Window,
+            output: PathBuf::from(DEFAULT_OUTPUT),
+            format: OutputFormat::Tga { rle: true, origin: Origin::BottomLeft },
+            width: 1600,
+            height: 800,
+            mode: RenderMode::Lit,
+            color: RgbColor::new(Ergbcolor::WHITE),
+            background: None,
+            texture: None,
+            normal_map: None,
+            camera: Camera::perspective(Vector3D {x: 0.0, y: 0.0, z: 3.0}, Vector3D {x: 0.0, y: 0.0, z: 0.0}),
+            scale: 0.9,
+            light: Vector3D {x: 0.0, y: 0.0, z: -1.0},
+            tone_mapping: ToneMapping::Clamp,
+            exposure: 1.0,
+        }
+    }
+}
+
+/**
+ * Parse arguments without the program name, `None` means help was asked for.
+ * Both `--flag value` and `--flag=value` are accepted.
+ */
+pub fn parse(args: &[String]) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    let mut backend = None;
+    // first argument that a scene file would silently override
+    let mut conflict: Option<String> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            options.models.push(PathBuf::from(arg));
+            conflict = conflict.or(Some(arg.clone()));
+            continue;
+        }
+        let (flag, inline) = match arg.find('=') {
+            Some(at) if arg.starts_with("--") => (&arg[..at], Some(arg[at + 1..].to_string())),
+            _ => (&arg[..], None),
+        };
+        if flag != "--scene" && flag != "-h" && flag != "--help" {
+            conflict = conflict.or(Some(flag.to_string()));
+        }
+        let mut value = || -> Result<String, String> {
+            match inline.clone() {
+                Some(value) => Ok(value),
+                None => args.next().cloned().ok_or(format!("{} needs a value", flag)),
+            }
+        };
+        match flag {
+            "-h" | "--help" => return Ok(None),
+            "--scene" => options.scene = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => {
+                let output = PathBuf::from(value()?);
+                options.format = output_format(&output)?;
+                options.output = output;
+                backend = backend.or(Some(Backend::File));
+            },
+            "-b" | "--backend" => backend = Some(match value()?.as_str() {
+                "window" => Backend::Window,
+                "file" => Backend::File,
+                other => return Err(format!("Unknown backend '{}', expected window or file", other)),
+            }),
+            "-s" | "--size" => {
+                let (width, height) = parse_size(&value()?)?;
+                options.width = width;
+                options.height = height;
+            },
+            "-m" | "--mode" => options.mode = match value()?.as_str() {
+                "wire" => RenderMode::Wire,
+                "flat" => RenderMode::Flat,
+                "random" => RenderMode::Random,
+                "lit" => RenderMode::Lit,
+                "vertex_colors" => RenderMode::VertexColors,
+                other => return Err(format!("Unknown render mode '{}', expected wire, flat, random, lit or vertex_colors", other)),
+            },
+            "-c" | "--color" => options.color = parse_color(&value()?)?,
+            "--background" => options.background = Some(parse_color(&value()?)?),
+            "-t" | "--texture" => options.texture = Some(PathBuf::from(value()?)),
+            "-n" | "--normal-map" => options.normal_map = Some(PathBuf::from(value()?)),
+            "--eye" => options.camera.eye = parse_vector(&value()?)?,
+            "--target" => options.camera.target = parse_vector(&value()?)?,
+            "--fov" => options.camera.fov = field_of_view(parse_number(flag, &value()?)?)?,
+            "--orthographic" => options.camera.projection = Projection::Orthographic,
+            "--scale" => {
+                let scale = parse_number(flag, &value()?)?;
+                if scale <= 0.0 {
+                    return Err(format!("Scale {} has to be positive", scale));
+                }
+                options.scale = scale;
+            },
+            "--light" => options.light = parse_vector(&value()?)?,
+            "--tone-mapping" => options.tone_mapping = tone_mapping(&value()?)?,
+            "--exposure" => {
+                let exposure = parse_number(flag, &value()?)?;
+                if exposure <= 0.0 {
+                    return Err(format!("Exposure {} has to be positive", exposure));
+                }
+                options.exposure = exposure;
+            },
+            _ => return Err(format!("Unknown option '{}'", flag)),
+        }
+    }
+    if let (Some(_), Some(conflict)) = (options.scene.as_ref(), conflict) {
+        return Err(format!("--scene can't be combined with '{}'", conflict));
+    }
+    if (options.camera.eye - options.camera.target).abs() == 0.0 {
+        return Err("Camera eye and target are the same point".to_string());
+    }
+    if !options.camera.has_valid_up() {
+        return Err("Camera looks straight up or down, move --eye off the vertical through --target".to_string());
+    }
+    if options.light.abs() == 0.0 {
+        return Err("Light direction can't be zero".to_string());
+    }
+    if options.models.is_empty() {
+        options.models.push(PathBuf::from(DEFAULT_MODEL));
+    }
+    options.backend = backend.unwrap_or(Backend::Window);
+    Ok(Some(options))
+}
+
+/** Image format by the file extension */
+pub fn output_format(path: &Path) -> Result<OutputFormat, String> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase());
+    match extension.as_ref().map(|extension| extension.as_str()) {
+        Some("tga") => Ok(OutputFormat::Tga { rle: true, origin: Origin::BottomLeft }),
+        Some("png") => Ok(OutputFormat::Png),
+        _ => Err(format!("Can't tell the image format of '{}', use .tga or .png", path.display())),
+    }
+}
+
+/** Tone mapping operator by name */
+pub fn tone_mapping(name: &str) -> Result<ToneMapping, String> {
+    match name {
+        "clamp" => Ok(ToneMapping::Clamp),
+        "reinhard" => Ok(ToneMapping::Reinhard),
+        "aces" => Ok(ToneMapping::Aces),
+        "exposure" => Ok(ToneMapping::Exposure),
+        other => Err(format!("Unknown tone mapping '{}', expected clamp, reinhard, aces or exposure", other)),
+    }
+}
+
+/** Vertical field of view in radians, for options and scene files alike */
+pub fn field_of_view(degrees: f64) -> Result<f64, String> {
+    if degrees < 1.0 || degrees > 179.0 {
+        return Err(format!("Field of view {} is out of [1, 179] degrees", degrees));
+    }
+    Ok(degrees.to_radians())
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err(format!("{} expects a number, got '{}'", flag, value)),
+    }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let error = || format!("Size '{}' isn't WIDTHxHEIGHT", value);
+    let mut parts = value.split('x');
+    let width = parts.next().and_then(|width| width.parse::<u32>().ok()).ok_or_else(error)?;
+    let height = parts.next().and_then(|height| height.parse::<u32>().ok()).ok_or_else(error)?;
+    if parts.next().is_some() {
+        return Err(error());
+    }
+    if width == 0 || height == 0 || width > 0xFFFF || height > 0xFFFF {
+        return Err(format!("Size {}x{} is out of 1..65535", width, height));
+    }
+    Ok((width, height))
+}
+
+fn parse_vector(value: &str) -> Result<Vector3D, String> {
+    let coordinates: Vec<f64> = value.split(',')
+        .map(|coordinate| coordinate.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Vector '{}' isn't X,Y,Z", value))?;
+    if coordinates.len() != 3 || coordinates.iter().any(|coordinate| !coordinate.is_finite()) {
+        return Err(format!("Vector '{}' isn't X,Y,Z", value));
+    }
+    Ok(Vector3D {x: coordinates[0], y: coordinates[1], z: coordinates[2]})
+}
+
+/** Color name or hex RRGGBB / RRGGBBAA, optionally prefixed with # */
+pub fn parse_color(value: &str) -> Result<RgbColor, String> {
+    let named = match value.to_lowercase().as_str() {
+        "red" => Some(Ergbcolor::RED),
+        "green" => Some(Ergbcolor::GREEN),
+        "blue" => Some(Ergbcolor::BLUE),
+        "ice" => Some(Ergbcolor::ICE),
+        "iceblue" => Some(Ergbcolor::ICEBLUE),
+        "white" => Some(Ergbcolor::WHITE),
+        "gray" => Some(Ergbcolor::GRAY),
+        "darkgray" => Some(Ergbcolor::DARKGRAY),
+        "black" => Some(Ergbcolor::BLACK),
+        _ => None,
+    };
+    if let Some(named) = named {
+        return Ok(RgbColor::new(named));
+    }
+    let hex = value.trim_start_matches('#');
+    let number = u32::from_str_radix(hex, 16).map_err(|_| format!("Unknown color '{}'", value))?;
+    match hex.len() {
+        6 => Ok(RgbColor::from_value(0xFF000000 | number)),
+        8 => Ok(RgbColor::from_value(number >> 8 | number << (8*3))),
+        _ => Err(format!("Unknown color '{}'", value)),
+    }
+}
+
+#[test]
+fn command_line() {
+    let args = |line: &str| -> Vec<String> { line.split_whitespace().map(|arg| arg.to_string()).collect() };
+    let options = parse(&args("a.obj -o out.png --size=640x480 -m wire -c #FF000080 --eye 1,2,3 --fov 60 b.obj")).unwrap().unwrap();
+    assert_eq!(options.models, vec![PathBuf::from("a.obj"), PathBuf::from("b.obj")]);
+    assert_eq!(options.backend, Backend::File);
+    assert_eq!(options.format, OutputFormat::Png);
+    assert_eq!((options.width, options.height), (640, 480));
+    assert_eq!(options.mode, RenderMode::Wire);
+    assert_eq!(options.color.value(), 0x80FF0000);
+    assert_eq!((options.camera.eye.x, options.camera.eye.y, options.camera.eye.z), (1.0, 2.0, 3.0));
+    assert!((options.camera.fov - std::f64::consts::PI / 3.0).abs() < 1e-12);
+
+    let options = parse(&[]).unwrap().unwrap();
+    assert_eq!(options.models, vec![PathBuf::from(DEFAULT_MODEL)]);
+    assert_eq!(options.backend, Backend::Window);
+    assert_eq!(parse_color("gray").unwrap().value(), RgbColor::new(Ergbcolor::GRAY).value());
+    assert!(parse(&args("--help")).unwrap().is_none());
+    assert!(parse(&args("--scene s.json")).unwrap().unwrap().scene.is_some());
+    assert_eq!(parse(&args("-n obj/bumps_nm.tga")).unwrap().unwrap().normal_map, Some(PathBuf::from("obj/bumps_nm.tga")));
+    let options = parse(&args("--tone-mapping reinhard --exposure 2")).unwrap().unwrap();
+    assert_eq!((options.tone_mapping, options.exposure), (ToneMapping::Reinhard, 2.0));
+
+    for bad in ["--mode shiny", "--size 640", "--size 0x10", "-o out.bmp", "--color nope", "--eye 1,2",
+                "--fov", "--frobnicate", "--eye 0,0,0", "--fov 180",
+                "--scene s.json a.obj", "--scene s.json -m wire", "--tone-mapping filmic", "--exposure 0",
+                "--eye 0,3,0", "--fov 0.5", "--fov 179.5", "--scale 0", "--scale -1", "--scale nan"].iter() {
+        let error = parse(&args(bad)).err();
+        println!("{}: {:?}", bad, error);
+        assert!(error.is_some());
+    }
+}
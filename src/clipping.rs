@@ -0,0 +1,166 @@
+use crate::geometry::{Vector3D, Vector4D};
+
+/** Vertex of a clipped polygon, `bc` are barycentric weights relative to the source triangle */
+#[derive(Clone, Copy)]
+pub struct ClipVertex {
+    pub position: Vector4D,
+    pub bc: Vector3D,
+}
+
+/** Signed distance to one of the six planes of the clip space cube, inside is positive */
+fn plane_distance(v: &Vector4D, plane: usize) -> f64 {
+    match plane {
+        0 => v.w + v.x, // left
+        1 => v.w - v.x, // right
+        2 => v.w + v.y, // bottom
+        3 => v.w - v.y, // top
+        4 => v.w + v.z, // near
+        _ => v.w - v.z, // far
+    }
+}
+
+fn lerp(a: &Vector4D, b: &Vector4D, t: f64) -> Vector4D {
+    Vector4D {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+        z: a.z + (b.z - a.z) * t,
+        w: a.w + (b.w - a.w) * t,
+    }
+}
+
+/** Sutherland-Hodgman clipping of a clip space triangle against the view frustum */
+pub fn clip_triangle(clip: &[Vector4D; 3]) -> Vec<ClipVertex> {
+    let mut polygon = vec![
+        ClipVertex {position: clip[0], bc: Vector3D {x: 1.0, y: 0.0, z: 0.0}},
+        ClipVertex {position: clip[1], bc: Vector3D {x: 0.0, y: 1.0, z: 0.0}},
+        ClipVertex {position: clip[2], bc: Vector3D {x: 0.0, y: 0.0, z: 1.0}},
+    ];
+    for plane in 0..6 {
+        let mut result = Vec::with_capacity(polygon.len() + 1);
+        for i in 0..polygon.len() {
+            let current = polygon[i];
+            let next = polygon[(i + 1) % polygon.len()];
+            let d_current = plane_distance(&current.position, plane);
+            let d_next = plane_distance(&next.position, plane);
+            if d_current >= 0.0 {
+                result.push(current);
+            }
+            if (d_current >= 0.0) != (d_next >= 0.0) {
+                let t = d_current / (d_current - d_next);
+                result.push(ClipVertex {
+                    position: lerp(&current.position, &next.position, t),
+                    bc: current.bc + (next.bc - current.bc) * t,
+                });
+            }
+        }
+        polygon = result;
+        if polygon.is_empty() {
+            break;
+        }
+    }
+    polygon
+}
+
+/** Liang-Barsky clipping of a clip space segment against the view frustum */
+pub fn clip_segment(a: &Vector4D, b: &Vector4D) -> Option<(Vector4D, Vector4D)> {
+    let mut t0: f64 = 0.0;
+    let mut t1: f64 = 1.0;
+    for plane in 0..6 {
+        let d_a = plane_distance(a, plane);
+        let d_b = plane_distance(b, plane);
+        if d_a < 0.0 && d_b < 0.0 {
+            return None;
+        }
+        if d_a < 0.0 {
+            t0 = t0.max(d_a / (d_a - d_b));
+        } else if d_b < 0.0 {
+            t1 = t1.min(d_a / (d_a - d_b));
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((lerp(a, b, t0), lerp(a, b, t1)))
+}
+
+const INSIDE: u8 = 0;
+const LEFT: u8 = 1;
+const RIGHT: u8 = 2;
+const BOTTOM: u8 = 4;
+const TOP: u8 = 8;
+
+fn outcode(x: f64, y: f64, x_max: f64, y_max: f64) -> u8 {
+    let mut code = INSIDE;
+    if x < 0.0 {
+        code |= LEFT;
+    } else if x > x_max {
+        code |= RIGHT;
+    }
+    if y < 0.0 {
+        code |= BOTTOM;
+    } else if y > y_max {
+        code |= TOP;
+    }
+    code
+}
+
+/** Cohen-Sutherland clipping of a segment to the [0, x_max] x [0, y_max] rectangle */
+pub fn clip_line(x0: f64, y0: f64, x1: f64, y1: f64, x_max: f64, y_max: f64) -> Option<(f64, f64, f64, f64)> {
+    let (mut x0, mut y0, mut x1, mut y1) = (x0, y0, x1, y1);
+    let mut code0 = outcode(x0, y0, x_max, y_max);
+    let mut code1 = outcode(x1, y1, x_max, y_max);
+    loop {
+        if code0 | code1 == INSIDE {
+            return Some((x0, y0, x1, y1));
+        }
+        if code0 & code1 != INSIDE {
+            return None;
+        }
+        // move the outside point to the boundary it crosses
+        let code = if code0 != INSIDE { code0 } else { code1 };
+        let (x, y) = if code & TOP != 0 {
+            (x0 + (x1 - x0) * (y_max - y0) / (y1 - y0), y_max)
+        } else if code & BOTTOM != 0 {
+            (x0 + (x1 - x0) * (0.0 - y0) / (y1 - y0), 0.0)
+        } else if code & RIGHT != 0 {
+            (x_max, y0 + (y1 - y0) * (x_max - x0) / (x1 - x0))
+        } else {
+            (0.0, y0 + (y1 - y0) * (0.0 - x0) / (x1 - x0))
+        };
+        if code == code0 {
+            x0 = x;
+            y0 = y;
+            code0 = outcode(x0, y0, x_max, y_max);
+        } else {
+            x1 = x;
+            y1 = y;
+            code1 = outcode(x1, y1, x_max, y_max);
+        }
+    }
+}
+
+#[test]
+fn clip_line_to_rectangle() {
+    assert_eq!(clip_line(-100.0, 0.0, 50.0, 50.0, 99.0, 99.0).map(|l| l.0), Some(0.0));
+    assert_eq!(clip_line(0.0, 0.0, 500.0, 50.0, 99.0, 99.0), Some((0.0, 0.0, 99.0, 9.9)));
+    assert_eq!(clip_line(10.0, 10.0, 20.0, 20.0, 99.0, 99.0), Some((10.0, 10.0, 20.0, 20.0)));
+    assert!(clip_line(-10.0, -10.0, -1.0, 200.0, 99.0, 99.0).is_none());
+}
+
+#[test]
+fn clip_triangle_by_near_plane() {
+    let clip = [Vector4D {x: 0.0, y: 0.0, z: -2.0, w: 1.0},
+                Vector4D {x: 0.5, y: 0.0, z: 0.0, w: 1.0},
+                Vector4D {x: 0.0, y: 0.5, z: 0.0, w: 1.0}];
+    let polygon = clip_triangle(&clip);
+    assert_eq!(polygon.len(), 4);
+    for v in polygon.iter() {
+        println!("Clipped vertex {} (bc {})", v.position, v.bc);
+        assert!(v.position.z >= -v.position.w - 1e-9);
+        assert!((v.bc.x + v.bc.y + v.bc.z - 1.0).abs() < 1e-9);
+    }
+    let behind = [Vector4D {x: 0.0, y: 0.0, z: -2.0, w: 1.0}; 3];
+    assert!(clip_triangle(&behind).is_empty());
+    assert!(clip_segment(&clip[0], &clip[1]).is_some());
+    assert!(clip_segment(&behind[0], &behind[1]).is_none());
+}
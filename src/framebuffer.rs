@@ -0,0 +1,208 @@
+use crate::multisample::{Antialiasing, SampleBuffer};
+use crate::blend::{self, BlendMode};
+use crate::hdr::{HdrBuffer, ToneMapping};
+use crate::canvas_trait::RgbColor;
+
+/** Canvas color storage, pixels are kept in image order: top row first */
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u32>,
+    /** Samples of anti-aliased rendering, `pixels` are up to date only after `resolve` */
+    samples: Option<SampleBuffer>,
+    /** Linear light accumulation, `pixels` hold a clipped preview until `resolve` */
+    hdr: Option<HdrBuffer>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Framebuffer {
+            width: width,
+            height: height,
+            pixels: vec![0; (width * height) as usize],
+            samples: None,
+            hdr: None,
+        }
+    }
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+    /** Index of the canvas point, canvas y axis goes up */
+    fn index(&self, x: i32, y: i32) -> Result<usize, String> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return Err("Out of bounds coordinates".to_string());
+        }
+        Ok(((self.height - 1 - y as u32) * self.width + x as u32) as usize)
+    }
+    pub fn set(&mut self, x: i32, y: i32, color: u32) -> Result<(), String> {
+        let index = self.index(x, y)?;
+        self.pixels[index] = color;
+        if let Some(ref mut samples) = self.samples {
+            samples.fill(x, y, color);
+        }
+        if let Some(ref mut hdr) = self.hdr {
+            hdr.set(x, y, RgbColor::from_value(color));
+        }
+        Ok(())
+    }
+    /** Composite `color` onto the pixel, returns the resulting pixel */
+    pub fn blend(&mut self, x: i32, y: i32, color: u32, mode: BlendMode) -> Result<u32, String> {
+        if self.hdr.is_some() {
+            return self.blend_color(x, y, RgbColor::from_value(color), mode);
+        }
+        let result = blend::blend(mode, color, self.get(x, y)?);
+        self.set(x, y, result)?;
+        Ok(result)
+    }
+    /** Composite `color` onto the pixel keeping values above 1 in HDR mode, returns the preview pixel */
+    pub fn blend_color(&mut self, x: i32, y: i32, color: RgbColor, mode: BlendMode) -> Result<u32, String> {
+        let index = self.index(x, y)?;
+        match self.hdr {
+            Some(ref mut hdr) => {
+                let result = blend::blend_linear(mode, color, hdr.get(x, y).unwrap());
+                hdr.set(x, y, result);
+                self.pixels[index] = result.value();
+                Ok(self.pixels[index])
+            },
+            None => self.blend(x, y, color.value(), mode),
+        }
+    }
+    pub fn get(&self, x: i32, y: i32) -> Result<u32, String> {
+        let index = self.index(x, y)?;
+        Ok(match self.samples {
+            Some(ref samples) => samples.resolve(x, y),
+            None => self.pixels[index],
+        })
+    }
+    pub fn clear(&mut self, color: u32) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = color;
+        }
+        if let Some(ref mut samples) = self.samples {
+            samples.clear(color);
+        }
+        if let Some(ref mut hdr) = self.hdr {
+            hdr.clear(RgbColor::from_value(color));
+        }
+    }
+    /** Switch sample storage, samples start with the current image */
+    pub fn set_antialiasing(&mut self, antialiasing: Antialiasing) -> Result<(), String> {
+        if self.hdr.is_some() && antialiasing != Antialiasing::None {
+            return Err("Anti-aliasing of HDR framebuffers is not supported".to_string());
+        }
+        self.resolve_samples();
+        self.samples = match antialiasing {
+            Antialiasing::None => None,
+            _ => {
+                let mut samples = SampleBuffer::new(self.width, self.height, antialiasing)?;
+                for y in 0..self.height as i32 {
+                    for x in 0..self.width as i32 {
+                        samples.fill(x, y, self.get(x, y)?);
+                    }
+                }
+                Some(samples)
+            },
+        };
+        Ok(())
+    }
+    pub fn get_antialiasing(&self) -> Antialiasing {
+        match self.samples {
+            Some(ref samples) => samples.get_antialiasing(),
+            None => Antialiasing::None,
+        }
+    }
+    pub fn get_samples(&self) -> Option<&SampleBuffer> {
+        self.samples.as_ref()
+    }
+    pub fn get_samples_mut(&mut self) -> Option<&mut SampleBuffer> {
+        self.samples.as_mut()
+    }
+    /** Switch linear light accumulation, it starts with the current image */
+    pub fn set_hdr(&mut self, enabled: bool) -> Result<(), String> {
+        if enabled && self.samples.is_some() {
+            return Err("Anti-aliasing of HDR framebuffers is not supported".to_string());
+        }
+        if !enabled {
+            self.hdr = None;
+            return Ok(());
+        }
+        if self.hdr.is_none() {
+            let mut hdr = HdrBuffer::new(self.width, self.height);
+            for y in 0..self.height as i32 {
+                for x in 0..self.width as i32 {
+                    hdr.set(x, y, RgbColor::from_value(self.get(x, y)?));
+                }
+            }
+            self.hdr = Some(hdr);
+        }
+        Ok(())
+    }
+    pub fn is_hdr(&self) -> bool {
+        self.hdr.is_some()
+    }
+    pub fn get_hdr(&self) -> Option<&HdrBuffer> {
+        self.hdr.as_ref()
+    }
+    /** Bring samples or HDR values into displayable pixels */
+    pub fn resolve(&mut self, tone_mapping: ToneMapping, exposure: f64) {
+        self.resolve_samples();
+        if let Some(ref hdr) = self.hdr {
+            for y in 0..self.height as i32 {
+                for x in 0..self.width as i32 {
+                    let index = ((self.height - 1 - y as u32) * self.width + x as u32) as usize;
+                    self.pixels[index] = hdr.tone_map(x, y, tone_mapping, exposure);
+                }
+            }
+        }
+    }
+    /** Downsample samples into pixels */
+    fn resolve_samples(&mut self) {
+        if let Some(ref samples) = self.samples {
+            for y in 0..self.height as i32 {
+                for x in 0..self.width as i32 {
+                    let index = ((self.height - 1 - y as u32) * self.width + x as u32) as usize;
+                    self.pixels[index] = samples.resolve(x, y);
+                }
+            }
+        }
+    }
+    /** 0xAARRGGBB pixels, top row first. Call `resolve` first when anti-aliased */
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+    /** Packed RGBA bytes, top row first */
+    pub fn to_rgba_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in self.pixels.iter() {
+            bytes.push((pixel >> (8*2)) as u8);
+            bytes.push((pixel >> (8*1)) as u8);
+            bytes.push(*pixel as u8);
+            bytes.push((pixel >> (8*3)) as u8);
+        }
+        bytes
+    }
+    /** Packed RGB bytes, top row first, alpha is dropped */
+    pub fn to_rgb_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in self.pixels.iter() {
+            bytes.push((pixel >> (8*2)) as u8);
+            bytes.push((pixel >> (8*1)) as u8);
+            bytes.push(*pixel as u8);
+        }
+        bytes
+    }
+}
+
+#[test]
+fn framebuffer_image_order() {
+    let mut framebuffer = Framebuffer::new(3, 2);
+    framebuffer.set(0, 0, 0x112233).unwrap();
+    framebuffer.set(2, 1, 0xFFFFFF).unwrap();
+    assert!(framebuffer.set(3, 0, 0xFFFFFF).is_err());
+    assert_eq!(framebuffer.get(0, 0), Ok(0x112233));
+    assert_eq!(framebuffer.pixels(), &[0, 0, 0xFFFFFF, 0x112233, 0, 0]);
+    assert_eq!(&framebuffer.to_rgb_bytes()[9..12], &[0x11, 0x22, 0x33]);
+}
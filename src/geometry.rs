@@ -5,7 +5,7 @@ use std::ops::Sub;
 use std::ops::Neg;
 use std::fmt;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub struct Vector3D {
     pub x: f64,
     pub y: f64,
@@ -121,7 +121,7 @@ fn vector_commutative_multiple() {
 
 
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Triangle {
     pub p0: Vector3D,
     pub p1: Vector3D,
@@ -129,11 +129,50 @@ pub struct Triangle {
 }
 
 impl Triangle {
+    pub fn vertex(&self, nth: usize) -> Vector3D {
+        match nth {
+            0 => self.p0,
+            1 => self.p1,
+            _ => self.p2,
+        }
+    }
+    pub fn set_vertex(&mut self, nth: usize, v: Vector3D) {
+        match nth {
+            0 => self.p0 = v,
+            1 => self.p1 = v,
+            _ => self.p2 = v,
+        }
+    }
     pub fn normal(&self) -> Vector3D {
         let v1 = self.p1 - self.p0;
         let v2 = self.p2 - self.p0;
         (v2 * v1).normalize()
     }
+    pub fn transform(&self, mat: &Matrix4) -> Triangle {
+        Triangle {
+            p0: mat.transform_point(self.p0),
+            p1: mat.transform_point(self.p1),
+            p2: mat.transform_point(self.p2),
+        }
+    }
+    /** Homogeneous coordinates of the vertices, before perspective divide */
+    pub fn to_clip(&self, mat: &Matrix4) -> [Vector4D; 3] {
+        [*mat * Vector4D::from_point(self.p0),
+         *mat * Vector4D::from_point(self.p1),
+         *mat * Vector4D::from_point(self.p2)]
+    }
+    /** Transform vertex normals with a normal matrix */
+    pub fn transform_normals(&self, mat: &Matrix3) -> Triangle {
+        Triangle {
+            p0: (*mat * self.p0).normalize(),
+            p1: (*mat * self.p1).normalize(),
+            p2: (*mat * self.p2).normalize(),
+        }
+    }
+    /** Value at barycentric coordinates, works for positions, UVs and normals */
+    pub fn interpolate(&self, bc: Vector3D) -> Vector3D {
+        self.p0 * bc.x + self.p1 * bc.y + self.p2 * bc.z
+    }
 }
 
 impl Div<f64> for Triangle {
@@ -167,4 +206,328 @@ impl Add<Vector3D> for Triangle {
             p2: self.p2 + offset.clone(),
         }
     }
-}
\ No newline at end of file
+}
+/** Homogeneous point, result of a projective transform */
+#[derive(Clone, Copy)]
+pub struct Vector4D {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Vector4D {
+    pub fn from_point(v: Vector3D) -> Vector4D {
+        Vector4D {x: v.x, y: v.y, z: v.z, w: 1.0}
+    }
+    pub fn from_direction(v: Vector3D) -> Vector4D {
+        Vector4D {x: v.x, y: v.y, z: v.z, w: 0.0}
+    }
+    /** Perspective divide */
+    pub fn to_point(&self) -> Vector3D {
+        Vector3D {x: self.x / self.w, y: self.y / self.w, z: self.z / self.w}
+    }
+    pub fn to_direction(&self) -> Vector3D {
+        Vector3D {x: self.x, y: self.y, z: self.z}
+    }
+}
+
+impl fmt::Display for Vector4D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}, {}, {}, {}]", self.x, self.y, self.z, self.w)
+    }
+}
+
+/** Row-major 3x3 matrix */
+#[derive(Clone, Copy)]
+pub struct Matrix3 {
+    pub m: [[f64; 3]; 3],
+}
+
+impl Matrix3 {
+    pub fn identity() -> Matrix3 {
+        Matrix3 {m: [[1.0, 0.0, 0.0],
+                     [0.0, 1.0, 0.0],
+                     [0.0, 0.0, 1.0]]}
+    }
+    /** Upper left 3x3 block of the matrix */
+    pub fn from_matrix4(mat: &Matrix4) -> Matrix3 {
+        let mut result = Matrix3::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                result.m[i][j] = mat.m[i][j];
+            }
+        }
+        result
+    }
+    pub fn transpose(&self) -> Matrix3 {
+        let mut result = Matrix3::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                result.m[i][j] = self.m[j][i];
+            }
+        }
+        result
+    }
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+    /** None for singular matrix, the determinant is compared with the product of row lengths */
+    pub fn inverse(&self) -> Option<Matrix3> {
+        let m = &self.m;
+        let det = self.determinant();
+        let scale: f64 = m.iter().map(|row| (row[0] * row[0] + row[1] * row[1] + row[2] * row[2]).sqrt()).product();
+        if !det.is_finite() || det.abs() <= std::f64::EPSILON * scale {
+            return None;
+        }
+        let mut result = Matrix3::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                // cofactor of (j, i) gives the adjugate directly
+                let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+                let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+                result.m[i][j] = (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det;
+            }
+        }
+        Some(result)
+    }
+    /** Matrix transforming normals the same way as the matrix transforms points */
+    pub fn normal_matrix(mat: &Matrix4) -> Matrix3 {
+        let linear = Matrix3::from_matrix4(mat);
+        linear.inverse().unwrap_or(linear).transpose()
+    }
+}
+
+impl Mul<Matrix3> for Matrix3 {
+    type Output = Matrix3;
+    fn mul(self, rhs: Matrix3) -> Matrix3 {
+        let mut result = Matrix3 {m: [[0.0; 3]; 3]};
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    result.m[i][j] += self.m[i][k] * rhs.m[k][j];
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Mul<Vector3D> for Matrix3 {
+    type Output = Vector3D;
+    fn mul(self, v: Vector3D) -> Vector3D {
+        let m = &self.m;
+        Vector3D {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+}
+
+/** Row-major 4x4 matrix, applied to column vectors */
+#[derive(Clone, Copy)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn identity() -> Matrix4 {
+        Matrix4 {m: [[1.0, 0.0, 0.0, 0.0],
+                     [0.0, 1.0, 0.0, 0.0],
+                     [0.0, 0.0, 1.0, 0.0],
+                     [0.0, 0.0, 0.0, 1.0]]}
+    }
+    pub fn translation(offset: Vector3D) -> Matrix4 {
+        let mut result = Matrix4::identity();
+        result.m[0][3] = offset.x;
+        result.m[1][3] = offset.y;
+        result.m[2][3] = offset.z;
+        result
+    }
+    pub fn scale(k: Vector3D) -> Matrix4 {
+        let mut result = Matrix4::identity();
+        result.m[0][0] = k.x;
+        result.m[1][1] = k.y;
+        result.m[2][2] = k.z;
+        result
+    }
+    /** Counter-clockwise rotation around the axis, angle in radians */
+    pub fn rotation(axis: Vector3D, angle: f64) -> Matrix4 {
+        let a = axis.normalize();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        Matrix4 {m: [[t * a.x * a.x + cos,       t * a.x * a.y - sin * a.z, t * a.x * a.z + sin * a.y, 0.0],
+                     [t * a.x * a.y + sin * a.z, t * a.y * a.y + cos,       t * a.y * a.z - sin * a.x, 0.0],
+                     [t * a.x * a.z - sin * a.y, t * a.y * a.z + sin * a.x, t * a.z * a.z + cos,       0.0],
+                     [0.0,                       0.0,                       0.0,                       1.0]]}
+    }
+    /** View matrix of a camera at `eye` looking at `target`, camera looks along -z */
+    pub fn look_at(eye: Vector3D, target: Vector3D, up: Vector3D) -> Matrix4 {
+        let z = (eye - target).normalize();
+        let x = (up * z).normalize();
+        let y = z * x;
+        Matrix4 {m: [[x.x, x.y, x.z, -x.scalar(eye)],
+                     [y.x, y.y, y.z, -y.scalar(eye)],
+                     [z.x, z.y, z.z, -z.scalar(eye)],
+                     [0.0, 0.0, 0.0, 1.0]]}
+    }
+    /** OpenGL style projection to [-1, 1] cube, `fov_y` in radians */
+    pub fn perspective(fov_y: f64, aspect: f64, near: f64, far: f64) -> Matrix4 {
+        let f = 1.0 / (fov_y / 2.0).tan();
+        Matrix4 {m: [[f / aspect, 0.0, 0.0,                          0.0],
+                     [0.0,        f,   0.0,                          0.0],
+                     [0.0,        0.0, (far + near) / (near - far), 2.0 * far * near / (near - far)],
+                     [0.0,        0.0, -1.0,                         0.0]]}
+    }
+    pub fn orthographic(left: f64, right: f64, bottom: f64, top: f64, near: f64, far: f64) -> Matrix4 {
+        Matrix4 {m: [[2.0 / (right - left), 0.0, 0.0, -(right + left) / (right - left)],
+                     [0.0, 2.0 / (top - bottom), 0.0, -(top + bottom) / (top - bottom)],
+                     [0.0, 0.0, -2.0 / (far - near),  -(far + near) / (far - near)],
+                     [0.0, 0.0, 0.0, 1.0]]}
+    }
+    /**
+     * Maps [-1, 1] cube to the screen rectangle.
+     * Depth is flipped to [0, 1] so that bigger z is closer to the viewer.
+     */
+    pub fn viewport(x: f64, y: f64, width: f64, height: f64) -> Matrix4 {
+        Matrix4 {m: [[width / 2.0, 0.0, 0.0, x + width / 2.0],
+                     [0.0, height / 2.0, 0.0, y + height / 2.0],
+                     [0.0, 0.0, -0.5, 0.5],
+                     [0.0, 0.0, 0.0, 1.0]]}
+    }
+    pub fn transpose(&self) -> Matrix4 {
+        let mut result = Matrix4::identity();
+        for i in 0..4 {
+            for j in 0..4 {
+                result.m[i][j] = self.m[j][i];
+            }
+        }
+        result
+    }
+    /** Gauss-Jordan elimination, None for singular or non-finite matrix */
+    pub fn inverse(&self) -> Option<Matrix4> {
+        if self.m.iter().flatten().any(|value| !value.is_finite()) {
+            return None;
+        }
+        // pivots are compared relative to the largest entry so that uniformly scaled matrices invert alike
+        let scale = self.m.iter().flatten().fold(0.0f64, |acc, value| acc.max(value.abs()));
+        let mut a = self.m;
+        let mut result = Matrix4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() <= std::f64::EPSILON * scale {
+                return None;
+            }
+            a.swap(col, pivot);
+            result.swap(col, pivot);
+            let k = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= k;
+                result[col][j] /= k;
+            }
+            for i in 0..4 {
+                if i == col {
+                    continue;
+                }
+                let k = a[i][col];
+                for j in 0..4 {
+                    a[i][j] -= k * a[col][j];
+                    result[i][j] -= k * result[col][j];
+                }
+            }
+        }
+        Some(Matrix4 {m: result})
+    }
+    pub fn transform_point(&self, v: Vector3D) -> Vector3D {
+        (*self * Vector4D::from_point(v)).to_point()
+    }
+    pub fn transform_direction(&self, v: Vector3D) -> Vector3D {
+        (*self * Vector4D::from_direction(v)).to_direction()
+    }
+}
+
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut result = Matrix4 {m: [[0.0; 4]; 4]};
+        for i in 0..4 {
+            for j in 0..4 {
+                for k in 0..4 {
+                    result.m[i][j] += self.m[i][k] * rhs.m[k][j];
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Mul<Vector4D> for Matrix4 {
+    type Output = Vector4D;
+    fn mul(self, v: Vector4D) -> Vector4D {
+        let m = &self.m;
+        Vector4D {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z + m[0][3] * v.w,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z + m[1][3] * v.w,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3] * v.w,
+            w: m[3][0] * v.x + m[3][1] * v.y + m[3][2] * v.z + m[3][3] * v.w,
+        }
+    }
+}
+
+impl fmt::Display for Matrix4 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.m.iter() {
+            writeln!(f, "[{}, {}, {}, {}]", row[0], row[1], row[2], row[3])?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn matrix_inverse() {
+    let mat = Matrix4::translation(Vector3D {x: 1.0, y: -2.0, z: 3.0})
+        * Matrix4::rotation(Vector3D {x: 1.0, y: 1.0, z: 0.0}, 0.7)
+        * Matrix4::scale(Vector3D {x: 2.0, y: 3.0, z: 4.0});
+    let product = mat * mat.inverse().unwrap();
+    println!("M * M^-1 =\n{}", product);
+    for i in 0..4 {
+        for j in 0..4 {
+            let expected = if i == j { 1.0 } else { 0.0 };
+            assert!((product.m[i][j] - expected).abs() < 1e-9);
+        }
+    }
+    assert!(Matrix4::scale(Vector3D {x: 1.0, y: 0.0, z: 1.0}).inverse().is_none());
+    let linear = Matrix3::from_matrix4(&mat);
+    let product = linear * linear.inverse().unwrap();
+    assert!((product.m[0][0] - 1.0).abs() < 1e-9 && product.m[0][1].abs() < 1e-9);
+
+    let small = Matrix4::scale(Vector3D {x: 1e-6, y: 1e-6, z: 1e-6});
+    assert!((small.inverse().unwrap().m[0][0] - 1e6).abs() < 1e-3);
+    let small = Matrix3::from_matrix4(&small);
+    assert!((small.inverse().unwrap().m[1][1] - 1e6).abs() < 1e-3);
+    assert!(Matrix3::from_matrix4(&Matrix4::scale(Vector3D {x: 1.0, y: 0.0, z: 1.0})).inverse().is_none());
+    let mut broken = Matrix4::identity();
+    broken.m[1][0] = std::f64::NAN;
+    assert!(broken.inverse().is_none());
+    assert!(Matrix3::from_matrix4(&broken).inverse().is_none());
+}
+
+#[test]
+fn matrix_projection() {
+    let eye = Vector3D {x: 0.0, y: 0.0, z: 3.0};
+    let view = Matrix4::look_at(eye, Vector3D {x: 0.0, y: 0.0, z: 0.0}, Vector3D {x: 0.0, y: 1.0, z: 0.0});
+    let projection = Matrix4::perspective(std::f64::consts::PI / 2.0, 1.0, 1.0, 10.0);
+    let viewport = Matrix4::viewport(0.0, 0.0, 100.0, 100.0);
+    let mvp = viewport * projection * view;
+    let near = mvp.transform_point(Vector3D {x: 0.0, y: 0.0, z: 2.0});
+    let far = mvp.transform_point(Vector3D {x: 0.0, y: 0.0, z: -7.0});
+    println!("Near {}, far {}", near, far);
+    assert!((near.z - 1.0).abs() < 1e-9 && far.z.abs() < 1e-9);
+    assert!((near.x - 50.0).abs() < 1e-9);
+    let corner = mvp.transform_point(Vector3D {x: 2.0, y: 2.0, z: 1.0});
+    assert!((corner.x - 100.0).abs() < 1e-9 && (corner.y - 100.0).abs() < 1e-9);
+}
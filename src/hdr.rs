@@ -0,0 +1,100 @@
+use crate::geometry::Vector3D;
+use crate::canvas_trait::RgbColor;
+
+/** Operator bringing linear HDR color into [0, 1], after scaling by exposure */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ToneMapping {
+    /** Everything above 1 is clipped */
+    Clamp,
+    /** x / (1 + x) */
+    Reinhard,
+    /** Filmic curve, Narkowicz fit of ACES */
+    Aces,
+    /** 1 - e^-x, like film exposure */
+    Exposure,
+}
+
+impl ToneMapping {
+    pub fn apply(&self, color: Vector3D, exposure: f64) -> Vector3D {
+        let map = |x: f64| {
+            let x = (x * exposure).max(0.0);
+            match *self {
+                ToneMapping::Clamp => x.min(1.0),
+                ToneMapping::Reinhard => x / (1.0 + x),
+                ToneMapping::Aces => (x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)).min(1.0),
+                ToneMapping::Exposure => 1.0 - (-x).exp(),
+            }
+        };
+        Vector3D {x: map(color.x), y: map(color.y), z: map(color.z)}
+    }
+}
+
+/** Linear light RGBA pixels, canvas y axis goes up */
+pub struct HdrBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl HdrBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        HdrBuffer {
+            width: width,
+            height: height,
+            pixels: vec![[0.0; 4]; (width * height) as usize],
+        }
+    }
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return None;
+        }
+        Some((y as u32 * self.width + x as u32) as usize)
+    }
+    pub fn get(&self, x: i32, y: i32) -> Option<RgbColor> {
+        let pixel = self.pixels[self.index(x, y)?];
+        let linear = Vector3D {x: pixel[0] as f64, y: pixel[1] as f64, z: pixel[2] as f64};
+        Some(RgbColor::from_linear(linear).with_alpha((pixel[3] * 255.0).round() as u8))
+    }
+    pub fn set(&mut self, x: i32, y: i32, color: RgbColor) {
+        if let Some(index) = self.index(x, y) {
+            let linear = color.to_linear();
+            self.pixels[index] = [linear.x as f32, linear.y as f32, linear.z as f32, color.get_alpha() as f32 / 255.0];
+        }
+    }
+    pub fn clear(&mut self, color: RgbColor) {
+        let linear = color.to_linear();
+        for pixel in self.pixels.iter_mut() {
+            *pixel = [linear.x as f32, linear.y as f32, linear.z as f32, color.get_alpha() as f32 / 255.0];
+        }
+    }
+    /** Displayable 0xAARRGGBB color of the pixel */
+    pub fn tone_map(&self, x: i32, y: i32, tone_mapping: ToneMapping, exposure: f64) -> u32 {
+        match self.get(x, y) {
+            Some(color) => RgbColor::from_linear(tone_mapping.apply(color.to_linear(), exposure))
+                .with_alpha(color.get_alpha())
+                .value(),
+            None => 0,
+        }
+    }
+}
+
+#[test]
+fn tone_mapping_operators() {
+    let bright = Vector3D {x: 4.0, y: 1.0, z: 0.0};
+    for &operator in [ToneMapping::Clamp, ToneMapping::Reinhard, ToneMapping::Aces, ToneMapping::Exposure].iter() {
+        let mapped = operator.apply(bright, 1.0);
+        assert!(mapped.x <= 1.0 && mapped.x >= mapped.y && mapped.z == 0.0);
+    }
+    assert_eq!(ToneMapping::Reinhard.apply(bright, 1.0).y, 0.5);
+    assert!((ToneMapping::Exposure.apply(bright, 1.0).y - (1.0 - (-1.0f64).exp())).abs() < 1e-12);
+    // the filmic curve darkens colors that are already displayable
+    assert!((ToneMapping::Aces.apply(bright, 1.0).y - 2.54 / 3.16).abs() < 1e-12);
+    assert_eq!(ToneMapping::Clamp.apply(bright, 0.25).x, 1.0);
+    assert_eq!(ToneMapping::Clamp.apply(bright, 0.25).y, 0.25);
+    // values too bright for 8 bits still differ after mapping
+    let mut hdr = HdrBuffer::new(2, 1);
+    hdr.set(0, 0, RgbColor::from_linear(Vector3D {x: 2.0, y: 2.0, z: 2.0}));
+    hdr.set(1, 0, RgbColor::from_linear(Vector3D {x: 8.0, y: 8.0, z: 8.0}));
+    assert!(hdr.tone_map(0, 0, ToneMapping::Reinhard, 1.0) < hdr.tone_map(1, 0, ToneMapping::Reinhard, 1.0));
+    assert_eq!(hdr.tone_map(0, 0, ToneMapping::Clamp, 1.0), hdr.tone_map(1, 0, ToneMapping::Clamp, 1.0));
+}
@@ -0,0 +1,56 @@
+#[macro_use] extern crate scan_fmt;
+#[macro_use] extern crate log;
+extern crate image;
+extern crate serde_json;
+
+pub mod canvas_sdl;
+
+pub mod canvas_tga;
+
+pub mod canvas_memory;
+
+pub mod canvas_trait;
+pub use canvas_trait::{Canvas, RgbColor};
+
+pub mod model_trait;
+pub use model_trait::Model;
+
+pub mod model_obj;
+
+pub mod model_stl;
+
+pub mod model_ply;
+
+pub mod geometry;
+
+pub mod zbuffer;
+
+pub mod framebuffer;
+
+pub mod hdr;
+
+pub mod multisample;
+
+pub mod blend;
+
+pub mod tga;
+
+pub mod texture;
+
+pub mod material;
+
+pub mod camera;
+
+pub mod clipping;
+
+pub mod shader;
+pub use shader::{Shader, Uniforms, Varying};
+
+pub mod shadow;
+
+pub mod light;
+
+pub mod cli;
+
+
+pub mod scene;
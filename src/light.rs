@@ -0,0 +1,168 @@
+use crate::geometry::*;
+use crate::canvas_trait::{RgbColor, Ergbcolor};
+
+/** Distance falloff 1 / (constant + linear * d + quadratic * d^2) */
+#[derive(Clone, Copy)]
+pub struct Attenuation {
+    pub constant: f64,
+    pub linear: f64,
+    pub quadratic: f64,
+}
+
+impl Attenuation {
+    pub fn factor(&self, distance: f64) -> f64 {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation {
+            constant: 1.0,
+            linear: 0.09,
+            quadratic: 0.032,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum LightKind {
+    /** Parallel rays travelling in `direction` */
+    Directional { direction: Vector3D },
+    /** Shines from `position` to every side */
+    Point { position: Vector3D, attenuation: Attenuation },
+    /**
+     * Cone from `position` along `direction`.
+     * Full light inside `inner_angle`, fading out to none at `outer_angle`, half angles in radians.
+     */
+    Spot { position: Vector3D, direction: Vector3D, inner_angle: f64, outer_angle: f64, attenuation: Attenuation },
+}
+
+#[derive(Clone, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: RgbColor,
+    pub intensity: f64,
+}
+
+impl Light {
+    pub fn directional(direction: Vector3D) -> Self {
+        Light::white(LightKind::Directional { direction: direction.normalize() })
+    }
+    pub fn point(position: Vector3D) -> Self {
+        Light::white(LightKind::Point { position: position, attenuation: Attenuation::default() })
+    }
+    pub fn spot(position: Vector3D, direction: Vector3D, inner_angle: f64, outer_angle: f64) -> Self {
+        Light::white(LightKind::Spot {
+            position: position,
+            direction: direction.normalize(),
+            inner_angle: inner_angle,
+            outer_angle: outer_angle,
+            attenuation: Attenuation::default(),
+        })
+    }
+    fn white(kind: LightKind) -> Self {
+        Light {
+            kind: kind,
+            color: RgbColor::new(Ergbcolor::WHITE),
+            intensity: 1.0,
+        }
+    }
+    pub fn with_color(mut self, color: RgbColor) -> Self {
+        self.color = color;
+        self
+    }
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+    /** Same light in the space of `transform`, e.g. the view matrix for camera space */
+    pub fn transform(&self, transform: &Matrix4) -> Light {
+        let kind = match self.kind {
+            LightKind::Directional { direction } => LightKind::Directional {
+                direction: transform.transform_direction(direction).normalize(),
+            },
+            LightKind::Point { position, attenuation } => LightKind::Point {
+                position: transform.transform_point(position),
+                attenuation: attenuation,
+            },
+            LightKind::Spot { position, direction, inner_angle, outer_angle, attenuation } => LightKind::Spot {
+                position: transform.transform_point(position),
+                direction: transform.transform_direction(direction).normalize(),
+                inner_angle: inner_angle,
+                outer_angle: outer_angle,
+                attenuation: attenuation,
+            },
+        };
+        Light {
+            kind: kind,
+            color: self.color,
+            intensity: self.intensity,
+        }
+    }
+    /**
+     * Unit vector from the point towards the light and the light arriving there as rgb weights.
+     * The point is in the same space as the light, a point at the light position gets no light.
+     */
+    pub fn illuminate(&self, point: Vector3D) -> (Vector3D, Vector3D) {
+        let radiance = self.color.to_linear() * self.intensity;
+        match self.kind {
+            LightKind::Directional { direction } => (-direction, radiance),
+            LightKind::Point { position, attenuation } => {
+                let to_light = position - point;
+                let distance = to_light.abs();
+                if distance < 1e-12 {
+                    return (Vector3D::default(), Vector3D::default());
+                }
+                (to_light / distance, radiance * attenuation.factor(distance))
+            },
+            LightKind::Spot { position, direction, inner_angle, outer_angle, attenuation } => {
+                let to_light = position - point;
+                let distance = to_light.abs();
+                if distance < 1e-12 {
+                    return (Vector3D::default(), Vector3D::default());
+                }
+                let to_light = to_light / distance;
+                let cos = (-to_light).scalar(direction);
+                let (cos_inner, cos_outer) = (inner_angle.cos(), outer_angle.cos());
+                let cone = if cos >= cos_inner {
+                    1.0
+                } else if cos <= cos_outer {
+                    0.0
+                } else {
+                    // smooth edge of the cone
+                    let t = (cos - cos_outer) / (cos_inner - cos_outer);
+                    t * t * (3.0 - 2.0 * t)
+                };
+                (to_light, radiance * (attenuation.factor(distance) * cone))
+            },
+        }
+    }
+}
+
+#[test]
+fn light_kinds() {
+    let origin = Vector3D::default();
+    let up = Vector3D {x: 0.0, y: 1.0, z: 0.0};
+    let (to_light, radiance) = Light::directional(-up).with_intensity(0.5).illuminate(origin);
+    assert!((to_light - up).abs() < 1e-9);
+    assert!((radiance.x - 0.5).abs() < 1e-9);
+
+    let near = Light::point(up).illuminate(origin).1;
+    let far = Light::point(up * 10.0).illuminate(origin).1;
+    assert!(far.x < near.x);
+
+    let spot = Light::spot(up, -up, 0.2, 0.4).with_color(RgbColor::from_rgb(255, 0, 0));
+    let (_, center) = spot.illuminate(origin);
+    let (_, edge) = spot.illuminate(Vector3D {x: 0.3, y: 0.0, z: 0.0});
+    let (_, outside) = spot.illuminate(Vector3D {x: 1.0, y: 0.0, z: 0.0});
+    assert!(center.x > edge.x && edge.x > 0.0);
+    assert_eq!(outside.x, 0.0);
+    assert_eq!(center.y, 0.0);
+
+    // no direction to a light at the point itself
+    for light in [Light::point(up), spot].iter() {
+        let (to_light, radiance) = light.illuminate(up);
+        assert_eq!((to_light.abs(), radiance.x), (0.0, 0.0));
+    }
+}
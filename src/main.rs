@@ -1,59 +1,46 @@
-#[macro_use] extern crate scan_fmt;
-#[macro_use] extern crate log;
-extern crate image;
+extern crate rust_render;
 
-mod canvas_sdl;
-#[allow(unused_imports)]
-use canvas_sdl::SdlCanvas;
-
-mod canvas_tga;
-#[allow(unused_imports)]
-use canvas_tga::TgaCanvas;
-
-mod canvas_trait;
-use canvas_trait::Canvas;
-#[allow(unused)]
-use canvas_trait::RgbColor;
-use canvas_trait::Ergbcolor;
-
-mod model_trait;
-
-mod model_obj;
-use model_obj::ObjModel;
-
-mod geometry;
-use geometry::*;
-
-/** Image Size */
-const WIDTH: u32 = 1600;
-const HEIGHT: u32 = 800;
-const SIZE: f64 = HEIGHT as f64 * 0.45;
-
-/** Project test images */
-#[allow(dead_code)]
-const OBJ_AFRO_HEAD: &str = "obj/african_head.obj";
-#[allow(dead_code)]
-const OBJ_FROSTMOURNE: &str = "obj/frostmourne.obj";
-#[allow(dead_code)]
-const OBJ_VANGUARD: &str = "obj/vanguard.obj";
+use rust_render::canvas_sdl::SdlCanvas;
+use rust_render::canvas_tga::TgaCanvas;
+use rust_render::Canvas;
+use rust_render::cli::{self, Backend};
+use rust_render::scene::Scene;
 
 fn main() {
-    let afro_head: ObjModel = model_trait::Model::new(std::path::Path::new(OBJ_AFRO_HEAD));
-    let frostmourne: ObjModel = model_trait::Model::new(std::path::Path::new(OBJ_FROSTMOURNE));
-    let mut canvas: SdlCanvas = canvas_trait::Canvas::new(WIDTH, HEIGHT);
-    canvas.render_poly_lightning(Box::new(afro_head), SIZE,
-                       Vector3D {
-                           x: WIDTH as f64 * 0.33,
-                           y: HEIGHT as f64 * 0.50,
-                           z: 0.0
-                       },
-                       RgbColor::new(Ergbcolor::GRAY));
-    canvas.render_poly_lightning(Box::new(frostmourne), SIZE,
-                       Vector3D {
-                           x: WIDTH as f64 * 0.66,
-                           y: HEIGHT as f64 * 0.50,
-                           z: 0.0
-                       },
-                       RgbColor::new(Ergbcolor::ICEBLUE));
-    canvas.out().unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Result::Ok(Some(options)) => options,
+        Result::Ok(None) => {
+            println!("{}", cli::USAGE);
+            return;
+        },
+        Result::Err(err) => {
+            eprintln!("rust-render: {}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        },
+    };
+    let scene = match options.scene {
+        Some(ref path) => Scene::load(path),
+        None => Scene::from_options(&options),
+    };
+    let result = scene.and_then(|scene| {
+        let output = &scene.output;
+        match output.backend {
+            Backend::Window => {
+                let mut canvas: SdlCanvas = Canvas::new(output.width, output.height);
+                output.setup(&mut canvas)?;
+                scene.render(&mut canvas).and_then(|_| canvas.out())
+            },
+            Backend::File => {
+                let mut canvas: TgaCanvas = Canvas::new(output.width, output.height);
+                canvas.set_output(&output.path, output.format);
+                output.setup(&mut canvas)?;
+                scene.render(&mut canvas).and_then(|_| canvas.out())
+            },
+        }
+    });
+    if let Result::Err(err) = result {
+        eprintln!("rust-render: {}", err);
+        std::process::exit(1);
+    }
 }
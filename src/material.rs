@@ -0,0 +1,72 @@
+use crate::geometry::Vector3D;
+
+/** Phong reflection model coefficients */
+#[derive(Clone, Copy)]
+pub struct Material {
+    pub ambient: f64,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub shininess: f64,
+    /** Use Blinn-Phong half vector instead of the reflected light vector */
+    pub blinn: bool,
+}
+
+impl Material {
+    pub fn new(ambient: f64, diffuse: f64, specular: f64, shininess: f64) -> Self {
+        Material {
+            ambient: ambient,
+            diffuse: diffuse,
+            specular: specular,
+            shininess: shininess,
+            blinn: true,
+        }
+    }
+    /** Plain Lambert diffuse surface without ambient and highlights */
+    pub fn lambert() -> Self {
+        Material::new(0.0, 1.0, 0.0, 1.0)
+    }
+    /**
+     * Returns (ambient + diffuse, specular) intensities.
+     * All vectors are normalized and point away from the surface.
+     */
+    pub fn phong(&self, normal: Vector3D, light: Vector3D, view: Vector3D) -> (f64, f64) {
+        let (diffuse, specular) = self.reflect(normal, light, view);
+        (self.ambient + diffuse, specular)
+    }
+    /** Diffuse and specular parts of `phong`, ambient is left out so that lights can be summed */
+    pub fn reflect(&self, normal: Vector3D, light: Vector3D, view: Vector3D) -> (f64, f64) {
+        let diffuse = normal.scalar(light).max(0.0);
+        if diffuse <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let specular = if self.blinn {
+            let half = (light + view).normalize();
+            normal.scalar(half).max(0.0)
+        } else {
+            let reflected = normal * (2.0 * normal.scalar(light)) - light;
+            reflected.scalar(view).max(0.0)
+        };
+        (self.diffuse * diffuse, self.specular * specular.powf(self.shininess))
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::new(0.1, 0.8, 0.6, 32.0)
+    }
+}
+
+#[test]
+fn material_phong() {
+    let n = Vector3D {x: 0.0, y: 0.0, z: 1.0};
+    let material = Material::default();
+    let (diffuse, specular) = material.phong(n, n, n);
+    assert!((diffuse - 0.9).abs() < 1e-9);
+    assert!((specular - 0.6).abs() < 1e-9);
+    let (diffuse, specular) = material.phong(n, -n, n);
+    assert_eq!(diffuse, material.ambient);
+    assert_eq!(specular, 0.0);
+    let light = Vector3D {x: 1.0, y: 0.0, z: 1.0}.normalize();
+    let phong = Material {blinn: false, ..material};
+    println!("Blinn-Phong: {:?}, Phong: {:?}", material.phong(n, light, n), phong.phong(n, light, n));
+}
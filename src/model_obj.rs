@@ -4,7 +4,7 @@ use std::io::BufReader;
 use std::io::BufRead;
 use std::fs::File;
 
-use crate::model_trait::Model;
+use crate::model_trait::{self, Model};
 use crate::geometry::{
     Vector3D,
     Triangle,
@@ -13,9 +13,45 @@ use crate::geometry::{
 #[derive(Clone)]
 pub struct ObjModel {
     triangles: Vec<Triangle>,
+    face_vertices: Vec<(usize, usize, usize)>,
+    tangents: Vec<Vector3D>,
+    bitangents: Vec<Vector3D>,
+    uv: Vec<Vector3D>,
+    face_uv: Vec<Option<(usize, usize, usize)>>,
+    normals: Vec<Vector3D>,
+    face_normals: Vec<Option<(usize, usize, usize)>>,
 }
 
 impl ObjModel {
+    fn empty() -> Self {
+        ObjModel {
+            triangles: Vec::new(),
+            face_vertices: Vec::new(),
+            tangents: Vec::new(),
+            bitangents: Vec::new(),
+            uv: Vec::new(),
+            face_uv: Vec::new(),
+            normals: Vec::new(),
+            face_normals: Vec::new(),
+        }
+    }
+
+    /** Model from an OBJ file, unreadable files and faces with indices out of range are errors */
+    pub fn load(file_path: &Path) -> Result<Self, String> {
+        info!("Loading model {}", file_path.display());
+        let file = File::open(file_path).map_err(|err| format!("Can't open {}: {}", file_path.display(), err))?;
+        let mut model = ObjModel::empty();
+        model.read_model(BufReader::new(file)).map_err(|err| format!("{}: {}", file_path.display(), err))?;
+        Ok(model)
+    }
+
+    /** Model from OBJ text */
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut model = ObjModel::empty();
+        model.read_model(bytes)?;
+        Ok(model)
+    }
+
     fn scan_vertex(&mut self, line: &str) -> Result<Vector3D, String> {
         let (x, y, z) = scan_fmt!(line, // input string
                         "v {} {} {}",  // format
@@ -33,54 +69,84 @@ impl ObjModel {
         }
     }
 
-    fn scan_face(&mut self, line: &str) -> Result<(usize, usize, usize), String> {
-        let (a, b, c) = scan_fmt!(line, // input string
-                        "f {}/{*}/{*} {}/{*}/{*} {}/{*}/{*}",  // format
-                        usize, usize, usize);   // type of a-c Options
+    fn scan_uv(&mut self, line: &str) -> Result<Vector3D, String> {
+        let (u, v) = scan_fmt!(line, // input string
+                        "vt {} {}",  // format
+                        f64, f64);   // type of u-v Options
+        if u.is_some() && v.is_some() {
+            debug!("Texture coordinate: {} {}", u.unwrap(), v.unwrap());
+            Ok(Vector3D {
+                x: u.unwrap(),
+                y: v.unwrap(),
+                z: 0.0,
+            })
+        }
+        else {
+            Err("Not a texture coordinate line format".to_string())
+        }
+    }
+
+    fn scan_normal(&mut self, line: &str) -> Result<Vector3D, String> {
+        let (x, y, z) = scan_fmt!(line, // input string
+                        "vn {} {} {}",  // format
+                        f64, f64, f64);   // type of a-c Options
+        if x.is_some() && y.is_some() && z.is_some() {
+            debug!("Normal: {} {} {}", x.unwrap(), y.unwrap(), z.unwrap());
+            Ok(Vector3D {
+                x: x.unwrap(),
+                y: y.unwrap(),
+                z: z.unwrap(),
+            }.normalize())
+        }
+        else {
+            Err("Not a vertex normal line format".to_string())
+        }
+    }
+
+    fn scan_face(&mut self, line: &str) -> Result<((usize, usize, usize), Option<(usize, usize, usize)>, Option<(usize, usize, usize)>), String> {
+        let (a, ta, na, b, tb, nb, c, tc, nc) = scan_fmt!(line, // input string
+                        "f {}/{}/{} {}/{}/{} {}/{}/{}",  // format
+                        usize, usize, usize, usize, usize, usize, usize, usize, usize);   // type of a-c Options
         if a.is_some() && b.is_some() && c.is_some() {
             debug!("Triangle: {}-{}-{}", a.unwrap(), b.unwrap(), c.unwrap());
-            return Ok((a.unwrap() as usize, b.unwrap() as usize, c.unwrap() as usize));
+            let uv = if ta.is_some() && tb.is_some() && tc.is_some() {
+                Some((ta.unwrap(), tb.unwrap(), tc.unwrap()))
+            } else {
+                None
+            };
+            let normals = if na.is_some() && nb.is_some() && nc.is_some() {
+                Some((na.unwrap(), nb.unwrap(), nc.unwrap()))
+            } else {
+                None
+            };
+            return Ok(((a.unwrap() as usize, b.unwrap() as usize, c.unwrap() as usize), uv, normals));
         }
         let (a, b, c) = scan_fmt!(line, // input string
                         "f {} {} {}",  // format
                         usize, usize, usize);   // type of a-c Options
         if a.is_some() && b.is_some() && c.is_some() {
             debug!("Triangle: {}-{}-{}", a.unwrap(), b.unwrap(), c.unwrap());
-            return Ok((a.unwrap() as usize, b.unwrap() as usize, c.unwrap() as usize));
+            return Ok(((a.unwrap() as usize, b.unwrap() as usize, c.unwrap() as usize), None, None));
         }
         Err("Not a face line format".to_string())
     }
 
-    fn read_model(&mut self, file_path: &Path) -> Result<(), String> {
-        // TODO: catch error
-        let str_path = file_path.to_str().unwrap();
-        println!("Loading model {}", str_path);
+    fn read_model<R: BufRead>(&mut self, reader: R) -> Result<(), String> {
         let mut vertex: Vec<Vector3D> = Vec::new();
 
-        let line_count = BufReader::new(&File::open(str_path).unwrap()).lines().count();
-        let f = File::open(str_path).unwrap();
-        let file = BufReader::new(&f);
+        let lines = reader.lines().collect::<Result<Vec<String>, _>>().map_err(|err| err.to_string())?;
+        let line_count = lines.len();
+        // every 10%, every line of short files
+        let step = std::cmp::max(line_count / 10, 1);
         let mut progress = 0;
-        let mut max_abs = 0.0;
-        for line in file.lines() {
-            let l = line.unwrap();
-
-            if progress % (10*line_count/100) == 0 {
-                println!("Loading progress: {}%", progress / (line_count/100));
+        for l in lines {
+            if progress % step == 0 {
+                info!("Loading progress: {}%", progress * 100 / line_count);
             }
             progress += 1;
 
             match self.scan_vertex(&l.clone()) {
                 Result::Ok(val) => {
-                    if val.x.abs() > max_abs {
-                        max_abs = val.x.abs();
-                    }
-                    if val.y.abs() > max_abs {
-                        max_abs = val.y.abs();
-                    }
-                    if val.z.abs() > max_abs {
-                        max_abs = val.z.abs();
-                    }
                     vertex.push(val);
                     continue;
                 }
@@ -88,38 +154,231 @@ impl ObjModel {
                 Result::Err(err) => debug!("Scan vertex error: {}", err),
             }
 
-            match self.scan_face(&l.clone()) {
+            match self.scan_uv(&l.clone()) {
+                Result::Ok(val) => {
+                    self.uv.push(val);
+                    continue;
+                }
+                // Skip error because of wrong format
+                Result::Err(err) => debug!("Scan texture coordinate error: {}", err),
+            }
+
+            match self.scan_normal(&l.clone()) {
                 Result::Ok(val) => {
-                    self.triangles.push(
-                        Triangle {
-                            p0: vertex[val.0 - 1].clone() / max_abs,
-                            p1: vertex[val.1 - 1].clone() / max_abs,
-                            p2: vertex[val.2 - 1].clone() / max_abs,
-                        }
-                    );
+                    self.normals.push(val);
+                    continue;
+                }
+                // Skip error because of wrong format
+                Result::Err(err) => debug!("Scan vertex normal error: {}", err),
+            }
+
+            match self.scan_face(&l.clone()) {
+                Result::Ok((val, uv, normals)) => {
+                    check_indices(progress, "vertex", val, vertex.len())?;
+                    if let Some(uv) = uv {
+                        check_indices(progress, "texture coordinate", uv, self.uv.len())?;
+                    }
+                    if let Some(normals) = normals {
+                        check_indices(progress, "normal", normals, self.normals.len())?;
+                    }
+                    self.face_vertices.push((val.0 - 1, val.1 - 1, val.2 - 1));
+                    self.face_uv.push(uv.map(|uv| (uv.0 - 1, uv.1 - 1, uv.2 - 1)));
+                    self.face_normals.push(normals.map(|n| (n.0 - 1, n.1 - 1, n.2 - 1)));
                     continue;
                 }
                 // Skip error because of wrong format
                 Result::Err(err) => debug!("Scan face error: {}", err),
             }
         }
-        println!("Triangles: {}", self.triangles.len());
+        let max_abs = model_trait::max_coordinate(vertex.iter());
+        self.triangles = self.face_vertices.iter()
+            .map(|&(a, b, c)| Triangle {p0: vertex[a] / max_abs, p1: vertex[b] / max_abs, p2: vertex[c] / max_abs})
+            .collect();
+        info!("Triangles: {}", self.triangles.len());
+        self.compute_tangents(vertex.len());
         Ok(())
     }
+
+    /**
+     * Per-vertex tangent basis, sum of the bases of the faces sharing the vertex.
+     * Where the sum cancels out, any basis perpendicular to the vertex normal is taken.
+     */
+    fn compute_tangents(&mut self, vertex_count: usize) {
+        self.tangents = vec![Vector3D::default(); vertex_count];
+        self.bitangents = vec![Vector3D::default(); vertex_count];
+        let mut normals = vec![Vector3D::default(); vertex_count];
+        for face in 0..self.triangles.len() {
+            let trgl = &self.triangles[face];
+            let (e1, e2) = (trgl.p1 - trgl.p0, trgl.p2 - trgl.p0);
+            let (a, b, c) = self.face_vertices[face];
+            for &i in [a, b, c].iter() {
+                normals[i] = normals[i] + e1 * e2; // area weighted
+            }
+            let uv = match self.triangle_uv(face) {
+                Some(uv) => uv,
+                None => continue,
+            };
+            let (du1, dv1) = (uv.p1.x - uv.p0.x, uv.p1.y - uv.p0.y);
+            let (du2, dv2) = (uv.p2.x - uv.p0.x, uv.p2.y - uv.p0.y);
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() < std::f64::EPSILON {
+                continue; // degenerate texture mapping
+            }
+            let tangent = (e1 * dv2 - e2 * dv1) / det;
+            let bitangent = (e2 * du1 - e1 * du2) / det;
+            for &i in [a, b, c].iter() {
+                self.tangents[i] = self.tangents[i] + tangent;
+                self.bitangents[i] = self.bitangents[i] + bitangent;
+            }
+        }
+        for i in 0..vertex_count {
+            let (tangent, bitangent) = (self.tangents[i], self.bitangents[i]);
+            if tangent.abs() < 1e-12 || bitangent.abs() < 1e-12 {
+                let (tangent, bitangent) = perpendicular_basis(normals[i]);
+                self.tangents[i] = tangent;
+                self.bitangents[i] = bitangent;
+            }
+            else {
+                self.tangents[i] = tangent.normalize();
+                self.bitangents[i] = bitangent.normalize();
+            }
+        }
+    }
+}
+
+/** One-based face indices of the line have to refer to one of the `count` items read so far */
+fn check_indices(line: usize, kind: &str, indices: (usize, usize, usize), count: usize) -> Result<(), String> {
+    for &index in [indices.0, indices.1, indices.2].iter() {
+        if index < 1 || index > count {
+            return Err(format!("line {}: face {} index {} is out of [1, {}]", line, kind, index, count));
+        }
+    }
+    Ok(())
+}
+
+/** Unit tangent and bitangent perpendicular to the normal, the xy basis for a zero normal */
+fn perpendicular_basis(normal: Vector3D) -> (Vector3D, Vector3D) {
+    if normal.abs() < 1e-12 {
+        return (Vector3D {x: 1.0, y: 0.0, z: 0.0}, Vector3D {x: 0.0, y: 1.0, z: 0.0});
+    }
+    let normal = normal.normalize();
+    // the axis least aligned with the normal keeps the cross product well conditioned
+    let axis = if normal.x.abs() <= normal.y.abs() && normal.x.abs() <= normal.z.abs() {
+        Vector3D {x: 1.0, y: 0.0, z: 0.0}
+    }
+    else if normal.y.abs() <= normal.z.abs() {
+        Vector3D {x: 0.0, y: 1.0, z: 0.0}
+    }
+    else {
+        Vector3D {x: 0.0, y: 0.0, z: 1.0}
+    };
+    let tangent = (axis * normal).normalize();
+    (tangent, normal * tangent)
 }
 
 impl Model for ObjModel {
+    /** Empty model if the file can't be loaded, `ObjModel::load` tells why */
     fn new(file_path: &Path) -> Self {
-        let mut model = ObjModel { 
-            triangles: Vec::new(),
-        };
-        match model.read_model(file_path) {
-            Result::Ok(_val) => println!("Model {} load successfully", file_path.to_str().unwrap()),
-            Result::Err(err) => println!("Model loading error: {}", err),
+        match ObjModel::load(file_path) {
+            Result::Ok(model) => {
+                println!("Model {} load successfully", file_path.display());
+                model
+            },
+            Result::Err(err) => {
+                println!("Model loading error: {}", err);
+                ObjModel::empty()
+            },
         }
-        model
     }
     fn triangle_iter(&self) -> Iter<Triangle> {
         self.triangles.iter()
     }
-}
\ No newline at end of file
+    fn triangle_uv(&self, face: usize) -> Option<Triangle> {
+        let (a, b, c) = self.face_uv[face]?;
+        Some(Triangle {
+            p0: *self.uv.get(a)?,
+            p1: *self.uv.get(b)?,
+            p2: *self.uv.get(c)?,
+        })
+    }
+    fn triangle_normals(&self, face: usize) -> Option<Triangle> {
+        let (a, b, c) = self.face_normals[face]?;
+        Some(Triangle {
+            p0: *self.normals.get(a)?,
+            p1: *self.normals.get(b)?,
+            p2: *self.normals.get(c)?,
+        })
+    }
+    fn triangle_tangents(&self, face: usize) -> Option<(Triangle, Triangle)> {
+        self.face_uv[face]?;
+        let (a, b, c) = self.face_vertices[face];
+        Some((Triangle {p0: self.tangents[a], p1: self.tangents[b], p2: self.tangents[c]},
+              Triangle {p0: self.bitangents[a], p1: self.bitangents[b], p2: self.bitangents[c]}))
+    }
+}
+#[test]
+fn load_uv_and_normals() {
+    let model: ObjModel = Model::new(Path::new("obj/african_head.obj"));
+    assert_eq!(model.triangle_iter().count(), 2492);
+    let uv = model.triangle_uv(0).unwrap();
+    println!("First face uv: {} {} {}", uv.p0, uv.p1, uv.p2);
+    let normals = model.triangle_normals(0).unwrap();
+    println!("First face normals: {} {} {}", normals.p0, normals.p1, normals.p2);
+    assert!((normals.p0.abs() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn tangent_basis() {
+    let model: ObjModel = Model::new(Path::new("obj/african_head.obj"));
+    let (tangents, bitangents) = model.triangle_tangents(0).unwrap();
+    let normals = model.triangle_normals(0).unwrap();
+    println!("First face tangent {}, bitangent {}", tangents.p0, bitangents.p0);
+    assert!((tangents.p0.abs() - 1.0).abs() < 1e-9);
+    // the basis is built from positions, so it lies close to the surface
+    assert!(tangents.p0.scalar(normals.p0).abs() < 0.5);
+    assert!(bitangents.p0.scalar(normals.p0).abs() < 0.5);
+}
+
+#[test]
+fn tangent_fallback() {
+    // vertex 0 is shared by two faces with mirrored uv, their tangents cancel out
+    let mut model = ObjModel {
+        triangles: vec![
+            Triangle {p0: Vector3D {x: 0.0, y: 0.0, z: 0.0}, p1: Vector3D {x: 1.0, y: 0.0, z: 0.0}, p2: Vector3D {x: 0.0, y: 1.0, z: 0.0}},
+            Triangle {p0: Vector3D {x: 0.0, y: 0.0, z: 0.0}, p1: Vector3D {x: -1.0, y: 0.0, z: 0.0}, p2: Vector3D {x: 0.0, y: -1.0, z: 0.0}},
+        ],
+        face_vertices: vec![(0, 1, 2), (0, 3, 4)],
+        tangents: Vec::new(),
+        bitangents: Vec::new(),
+        uv: vec![Vector3D {x: 0.0, y: 0.0, z: 0.0}, Vector3D {x: 1.0, y: 0.0, z: 0.0}, Vector3D {x: 0.0, y: 1.0, z: 0.0}],
+        face_uv: vec![Some((0, 1, 2)), Some((0, 1, 2))],
+        normals: Vec::new(),
+        face_normals: vec![None, None],
+    };
+    model.compute_tangents(5);
+    let (tangents, bitangents) = model.triangle_tangents(0).unwrap();
+    for &v in [tangents.p0, bitangents.p0].iter() {
+        assert!((v.abs() - 1.0).abs() < 1e-9);
+        assert!(v.z.abs() < 1e-9); // perpendicular to the z normal
+    }
+    assert!(tangents.p0.scalar(bitangents.p0).abs() < 1e-9);
+}
+
+#[test]
+fn load_errors() {
+    // shorter than ten lines
+    let model = ObjModel::from_bytes(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+    assert_eq!(model.triangle_iter().count(), 1);
+    assert_eq!(ObjModel::from_bytes(b"").unwrap().triangle_iter().count(), 0);
+
+    let errors = [
+        (&b"v 0 0 0\nv 1 0 0\nf 0 1 2\n"[..], "line 3: face vertex index 0 is out of [1, 2]"),
+        (&b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n"[..], "line 4: face vertex index 4 is out of [1, 3]"),
+        (&b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1/1 2/1/1 3/2/1\n"[..], "line 5: face texture coordinate index 2 is out of [1, 1]"),
+        (&b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1/0 2/1/0 3/1/0\n"[..], "line 5: face normal index 0 is out of [1, 0]"),
+    ];
+    for &(bytes, expected) in errors.iter() {
+        assert_eq!(ObjModel::from_bytes(bytes).err(), Some(expected.to_string()));
+    }
+    assert!(ObjModel::load(Path::new("obj/missing.obj")).is_err());
+}
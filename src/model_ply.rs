@@ -0,0 +1,397 @@
+use std::slice::Iter;
+use std::path::Path;
+use std::fs::File;
+use std::io::Read;
+
+use crate::model_trait::{self, Model};
+use crate::canvas_trait::RgbColor;
+use crate::geometry::{
+    Vector3D,
+    Triangle,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/** Scalar property type with its size in bytes */
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Scalar {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar, String> {
+        match name {
+            "char" | "int8" => Ok(Scalar::Int8),
+            "uchar" | "uint8" => Ok(Scalar::Uint8),
+            "short" | "int16" => Ok(Scalar::Int16),
+            "ushort" | "uint16" => Ok(Scalar::Uint16),
+            "int" | "int32" => Ok(Scalar::Int32),
+            "uint" | "uint32" => Ok(Scalar::Uint32),
+            "float" | "float32" => Ok(Scalar::Float32),
+            "double" | "float64" => Ok(Scalar::Float64),
+            _ => Err(format!("Unknown PLY property type '{}'", name)),
+        }
+    }
+    fn size(&self) -> usize {
+        match *self {
+            Scalar::Int8 | Scalar::Uint8 => 1,
+            Scalar::Int16 | Scalar::Uint16 => 2,
+            Scalar::Int32 | Scalar::Uint32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    /** Type of the item count for list properties */
+    list: Option<Scalar>,
+    scalar: Scalar,
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/** Property values in the body, ASCII words or binary numbers */
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        if self.format == Format::Ascii {
+            while self.offset < self.bytes.len() && (self.bytes[self.offset] as char).is_ascii_whitespace() {
+                self.offset += 1;
+            }
+            let start = self.offset;
+            while self.offset < self.bytes.len() && !(self.bytes[self.offset] as char).is_ascii_whitespace() {
+                self.offset += 1;
+            }
+            return std::str::from_utf8(&self.bytes[start..self.offset]).ok()
+                .and_then(|word| word.parse::<f64>().ok())
+                .ok_or_else(|| format!("Bad PLY value at byte {}", start));
+        }
+        let size = scalar.size();
+        let data = self.bytes.get(self.offset..self.offset + size).ok_or("PLY data is truncated")?;
+        self.offset += size;
+        let mut raw = [0u8; 8];
+        raw[..size].copy_from_slice(data);
+        if self.format == Format::BinaryBigEndian {
+            raw[..size].reverse();
+        }
+        Ok(match scalar {
+            Scalar::Int8 => raw[0] as i8 as f64,
+            Scalar::Uint8 => raw[0] as f64,
+            Scalar::Int16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::Uint16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::Int32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::Uint32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::Float32 => f32::from_bits(u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]])) as f64,
+            Scalar::Float64 => f64::from_bits(u64::from_le_bytes(raw)),
+        })
+    }
+}
+
+/** Polygon file format mesh or point cloud, with optional vertex normals and colors */
+#[derive(Clone)]
+pub struct PlyModel {
+    triangles: Vec<Triangle>,
+    face_vertices: Vec<(usize, usize, usize)>,
+    vertices: Vec<Vector3D>,
+    normals: Option<Vec<Vector3D>>,
+    colors: Option<Vec<RgbColor>>,
+}
+
+impl PlyModel {
+    /** Header lines up to the first one that is exactly `end_header`, the body starts after it */
+    fn read_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize), String> {
+        let mut offset = 0;
+        if header_line(bytes, &mut offset)? != "ply" {
+            return Err("Not a PLY file".to_string());
+        }
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        loop {
+            let line = header_line(bytes, &mut offset)?;
+            if line == "end_header" {
+                break;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["format", kind, _version] => format = Some(match *kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    other => return Err(format!("Unknown PLY format '{}'", other)),
+                }),
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count.parse().map_err(|_| format!("Bad element count in '{}'", line))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count, item, name] => elements.last_mut()
+                    .ok_or(format!("Property before any element: '{}'", line))?
+                    .properties.push(Property {name: name.to_string(), list: Some(Scalar::parse(count)?), scalar: Scalar::parse(item)?}),
+                ["property", scalar, name] => elements.last_mut()
+                    .ok_or(format!("Property before any element: '{}'", line))?
+                    .properties.push(Property {name: name.to_string(), list: None, scalar: Scalar::parse(scalar)?}),
+                _ if words.is_empty() || words[0] == "comment" || words[0] == "obj_info" => {},
+                _ => return Err(format!("Unknown PLY header line '{}'", line)),
+            }
+        }
+        Ok((format.ok_or("PLY header has no format")?, elements, offset))
+    }
+
+    /** Model from a PLY file, unreadable or malformed files are errors */
+    pub fn load(file_path: &Path) -> Result<Self, String> {
+        info!("Loading model {}", file_path.display());
+        let mut bytes = Vec::new();
+        File::open(file_path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|err| format!("Can't read {}: {}", file_path.display(), err))?;
+        PlyModel::from_bytes(&bytes).map_err(|err| format!("{}: {}", file_path.display(), err))
+    }
+
+    /** Model from the contents of an ASCII or binary PLY file */
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let (format, elements, body) = PlyModel::read_header(bytes)?;
+        let mut body = Body {format: format, bytes: bytes, offset: body};
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut colors = Vec::new();
+        let mut faces: Vec<Vec<usize>> = Vec::new();
+        for element in elements.iter() {
+            let find = |name: &str| element.properties.iter().position(|property| property.name == name);
+            let position = [find("x"), find("y"), find("z")];
+            let normal = [find("nx"), find("ny"), find("nz")];
+            let color = [find("red"), find("green"), find("blue")];
+            let indices = find("vertex_indices").or(find("vertex_index"));
+            for _ in 0..element.count {
+                let mut values = Vec::with_capacity(element.properties.len());
+                let mut list = Vec::new();
+                for (i, property) in element.properties.iter().enumerate() {
+                    match property.list {
+                        Some(count) => {
+                            let count = index(body.read(count)?)?;
+                            let items = (0..count).map(|_| body.read(property.scalar)).collect::<Result<Vec<f64>, String>>()?;
+                            if Some(i) == indices {
+                                list = items;
+                            }
+                            values.push(0.0);
+                        },
+                        None => values.push(body.read(property.scalar)?),
+                    }
+                }
+                let vector = |at: &[Option<usize>; 3]| -> Option<Vector3D> {
+                    Some(Vector3D {x: values[at[0]?], y: values[at[1]?], z: values[at[2]?]})
+                };
+                match element.name.as_str() {
+                    "vertex" => {
+                        vertices.push(vector(&position).ok_or("PLY vertices have no x, y, z")?);
+                        if let Some(normal) = vector(&normal) {
+                            normals.push(normal.normalize());
+                        }
+                        if let (Some(channels), Some(red)) = (vector(&color), color[0]) {
+                            // integer channels are 0-255, floating point ones 0-1
+                            let scale = match element.properties[red].scalar {
+                                Scalar::Float32 | Scalar::Float64 => 255.0,
+                                _ => 1.0,
+                            };
+                            let channel = |value: f64| (value * scale).round().max(0.0).min(255.0) as u8;
+                            colors.push(RgbColor::from_rgb(channel(channels.x), channel(channels.y), channel(channels.z)));
+                        }
+                    },
+                    "face" => faces.push(list.iter().map(|&value| index(value)).collect::<Result<Vec<usize>, String>>()?),
+                    // edges, materials and the like aren't drawn
+                    _ => {},
+                }
+            }
+        }
+        let max_abs = model_trait::max_coordinate(vertices.iter());
+        let vertices: Vec<Vector3D> = vertices.iter().map(|&vertex| vertex / max_abs).collect();
+        let mut triangles = Vec::new();
+        let mut face_vertices = Vec::new();
+        for face in faces.iter() {
+            if let Some(&index) = face.iter().find(|&&index| index >= vertices.len()) {
+                return Err(format!("PLY face refers to vertex {} of {}", index, vertices.len()));
+            }
+            // polygons are split into a fan
+            for i in 1..face.len().max(2) - 1 {
+                let (a, b, c) = (face[0], face[i], face[i + 1]);
+                triangles.push(Triangle {p0: vertices[a], p1: vertices[b], p2: vertices[c]});
+                face_vertices.push((a, b, c));
+            }
+        }
+        let normals = if normals.len() == vertices.len() && !normals.is_empty() { Some(normals) } else { None };
+        let colors = if colors.len() == vertices.len() && !colors.is_empty() { Some(colors) } else { None };
+        info!("Vertices: {}, triangles: {}", vertices.len(), triangles.len());
+        Ok(PlyModel {
+            triangles: triangles,
+            face_vertices: face_vertices,
+            vertices: vertices,
+            normals: normals,
+            colors: colors,
+        })
+    }
+}
+
+/** Header line starting at `offset` without the line break, `offset` moves to the next line */
+fn header_line<'a>(bytes: &'a [u8], offset: &mut usize) -> Result<&'a str, String> {
+    if *offset >= bytes.len() {
+        return Err("PLY header has no end_header".to_string());
+    }
+    let end = bytes[*offset..].iter().position(|&byte| byte == b'\n').map_or(bytes.len(), |newline| *offset + newline);
+    let line = std::str::from_utf8(&bytes[*offset..end]).map_err(|_| "PLY header isn't text")?;
+    *offset = (end + 1).min(bytes.len());
+    Ok(line.trim_end())
+}
+
+/** List counts and vertex indices are read as signed numbers, negative ones are rejected */
+fn index(value: f64) -> Result<usize, String> {
+    if value < 0.0 || value.fract() != 0.0 {
+        return Err(format!("PLY index {} isn't a whole number of at least 0", value));
+    }
+    Ok(value as usize)
+}
+
+impl Model for PlyModel {
+    /** Empty model if the file can't be loaded, `PlyModel::load` tells why */
+    fn new(file_path: &Path) -> Self {
+        match PlyModel::load(file_path) {
+            Result::Ok(model) => {
+                println!("Model {} load successfully", file_path.display());
+                model
+            },
+            Result::Err(err) => {
+                println!("Model loading error: {}", err);
+                PlyModel {
+                    triangles: Vec::new(),
+                    face_vertices: Vec::new(),
+                    vertices: Vec::new(),
+                    normals: None,
+                    colors: None,
+                }
+            },
+        }
+    }
+    fn triangle_iter(&self) -> Iter<Triangle> {
+        self.triangles.iter()
+    }
+    fn triangle_normals(&self, face: usize) -> Option<Triangle> {
+        let normals = self.normals.as_ref()?;
+        let (a, b, c) = self.face_vertices[face];
+        Some(Triangle {p0: normals[a], p1: normals[b], p2: normals[c]})
+    }
+    fn triangle_colors(&self, face: usize) -> Option<Triangle> {
+        let colors = self.colors.as_ref()?;
+        let (a, b, c) = self.face_vertices[face];
+        Some(Triangle {p0: colors[a].to_linear(), p1: colors[b].to_linear(), p2: colors[c].to_linear()})
+    }
+    fn points(&self) -> &[Vector3D] {
+        if self.triangles.is_empty() {
+            &self.vertices
+        } else {
+            &[]
+        }
+    }
+    fn point_color(&self, point: usize) -> Option<RgbColor> {
+        Some(self.colors.as_ref()?[point])
+    }
+}
+
+#[test]
+fn load_ply_formats() {
+    let header = |format: &str, faces: usize| format!("ply
+format {} 1.0
+comment two triangles of a quad
+comment end_header in a comment doesn't end the header
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face {}
+property list uchar int vertex_indices
+end_header
+", format, faces);
+    let positions = [(0.0f32, 0.0f32, 0.0f32), (2.0, 0.0, 0.0), (2.0, 2.0, 0.0), (0.0, 2.0, -4.0)];
+    let colors = [[255u8, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+
+    let mut ascii = header("ascii", 1);
+    for (position, color) in positions.iter().zip(colors.iter()) {
+        ascii += &format!("{} {} {} {} {} {}\n", position.0, position.1, position.2, color[0], color[1], color[2]);
+    }
+    ascii += "4 0 1 2 3\n";
+    let binary = |format: &str, big_endian: bool, faces: usize| {
+        let mut bytes = header(format, faces).into_bytes();
+        let float = |value: f32| if big_endian { value.to_bits().to_be_bytes() } else { value.to_bits().to_le_bytes() };
+        for (position, color) in positions.iter().zip(colors.iter()) {
+            for &value in [position.0, position.1, position.2].iter() {
+                bytes.extend_from_slice(&float(value));
+            }
+            bytes.extend_from_slice(color);
+        }
+        if faces > 0 {
+            bytes.push(4);
+            for &index in [0i32, 1, 2, 3].iter() {
+                bytes.extend_from_slice(&if big_endian { index.to_be_bytes() } else { index.to_le_bytes() });
+            }
+        }
+        bytes
+    };
+    // ascii, little and big endian meshes and a point cloud
+    let files = [
+        ascii.into_bytes(),
+        binary("binary_little_endian", false, 1),
+        binary("binary_big_endian", true, 1),
+        binary("binary_little_endian", false, 0),
+    ];
+    let models: Vec<PlyModel> = files.iter().map(|bytes| PlyModel::from_bytes(bytes).unwrap()).collect();
+    for model in models[..3].iter() {
+        assert_eq!(model.triangle_iter().count(), 2);
+        assert!(model.points().is_empty());
+        // normalized like OBJ, the largest coordinate is 4
+        assert_eq!(model.triangle(1).p2.z, -1.0);
+        assert_eq!(model.triangle(0).p1.x, 0.5);
+        let colors = model.triangle_colors(0).unwrap();
+        assert_eq!((colors.p0.x, colors.p1.y, colors.p2.z), (1.0, 1.0, 1.0));
+        assert!(model.triangle_normals(0).is_none());
+    }
+    let cloud = &models[3];
+    assert_eq!(cloud.triangle_iter().count(), 0);
+    assert_eq!(cloud.points().len(), 4);
+    assert_eq!(cloud.point_color(1).unwrap().value(), 0xFF00FF00);
+
+    // a negative index must not wrap around to the first vertex
+    let mut negative = binary("binary_little_endian", false, 1);
+    let last = negative.len() - 4;
+    negative[last..].copy_from_slice(&(-1i32).to_le_bytes());
+    assert_eq!(PlyModel::from_bytes(&negative).err(), Some("PLY index -1 isn't a whole number of at least 0".to_string()));
+    let mut outside = binary("binary_little_endian", false, 1);
+    outside[last..].copy_from_slice(&4i32.to_le_bytes());
+    assert_eq!(PlyModel::from_bytes(&outside).err(), Some("PLY face refers to vertex 4 of 4".to_string()));
+    assert!(PlyModel::from_bytes(&negative[..last]).is_err());
+    assert!(PlyModel::load(Path::new("obj/missing.ply")).is_err());
+    assert_eq!(PlyModel::from_bytes(b"ply\nformat ascii 1.0\ncomment end_header\n").err(), Some("PLY header has no end_header".to_string()));
+}
@@ -0,0 +1,213 @@
+use std::slice::Iter;
+use std::path::Path;
+use std::fs::File;
+use std::io::Read;
+
+use crate::model_trait::{self, Model};
+use crate::geometry::{
+    Vector3D,
+    Triangle,
+};
+
+const BINARY_HEADER_SIZE: usize = 80;
+/** Normal, three vertices and the attribute byte count */
+const BINARY_FACET_SIZE: usize = 50;
+
+/** Stereolithography mesh, ASCII or binary */
+#[derive(Clone)]
+pub struct StlModel {
+    triangles: Vec<Triangle>,
+    /** Facet normals from the file, `None` where the file leaves them zero */
+    normals: Vec<Option<Vector3D>>,
+}
+
+impl StlModel {
+    /** Model from an STL file, unreadable or malformed files are errors */
+    pub fn load(file_path: &Path) -> Result<Self, String> {
+        info!("Loading model {}", file_path.display());
+        let mut bytes = Vec::new();
+        File::open(file_path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|err| format!("Can't read {}: {}", file_path.display(), err))?;
+        StlModel::from_bytes(&bytes).map_err(|err| format!("{}: {}", file_path.display(), err))
+    }
+
+    /** Model from the contents of an ASCII or binary STL file */
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let facets = if StlModel::is_binary(bytes) {
+            StlModel::read_binary(bytes)?
+        } else {
+            let text = String::from_utf8_lossy(bytes);
+            StlModel::read_ascii(&text)?
+        };
+        let max_abs = model_trait::max_coordinate(facets.iter().flat_map(|facet| facet[1..].iter()));
+        let mut model = StlModel {
+            triangles: Vec::new(),
+            normals: Vec::new(),
+        };
+        for facet in facets.iter() {
+            model.triangles.push(Triangle {
+                p0: facet[1] / max_abs,
+                p1: facet[2] / max_abs,
+                p2: facet[3] / max_abs,
+            });
+            let normal = facet[0];
+            model.normals.push(if normal.abs() > 0.0 { Some(normal.normalize()) } else { None });
+        }
+        info!("Triangles: {}", model.triangles.len());
+        Ok(model)
+    }
+
+    /** Binary files have a fixed size, ASCII ones start with `solid` but binary headers may too */
+    fn is_binary(bytes: &[u8]) -> bool {
+        if bytes.len() >= BINARY_HEADER_SIZE + 4 {
+            let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+            if bytes.len() == BINARY_HEADER_SIZE + 4 + count * BINARY_FACET_SIZE {
+                return true;
+            }
+        }
+        !bytes.starts_with(b"solid")
+    }
+
+    fn read_binary(bytes: &[u8]) -> Result<Vec<[Vector3D; 4]>, String> {
+        if bytes.len() < BINARY_HEADER_SIZE + 4 {
+            return Err("Binary STL header is truncated".to_string());
+        }
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        let data = &bytes[BINARY_HEADER_SIZE + 4..];
+        if data.len() < count * BINARY_FACET_SIZE {
+            return Err(format!("Binary STL has {} bytes of facets, {} expected", data.len(), count * BINARY_FACET_SIZE));
+        }
+        let float = |at: usize| f32::from_bits(u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])) as f64;
+        let vector = |at: usize| Vector3D {x: float(at), y: float(at + 4), z: float(at + 8)};
+        Ok((0..count).map(|i| {
+            let at = i * BINARY_FACET_SIZE;
+            [vector(at), vector(at + 12), vector(at + 24), vector(at + 36)]
+        }).collect())
+    }
+
+    fn read_ascii(text: &str) -> Result<Vec<[Vector3D; 4]>, String> {
+        let mut facets = Vec::new();
+        let mut normal = Vector3D::default();
+        let mut polygon: Vec<Vector3D> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let coordinates = |from: usize| -> Result<Vector3D, String> {
+                let values: Vec<f64> = words[from..].iter()
+                    .map(|word| word.parse::<f64>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("Bad number at line {}", number + 1))?;
+                if values.len() != 3 {
+                    return Err(format!("Expected 3 coordinates at line {}", number + 1));
+                }
+                Ok(Vector3D {x: values[0], y: values[1], z: values[2]})
+            };
+            match words.first() {
+                Some(&"facet") => {
+                    debug!("Facet: {}", line);
+                    normal = match words.get(1) {
+                        Some(&"normal") => coordinates(2)?,
+                        _ => Vector3D::default(),
+                    };
+                    polygon.clear();
+                },
+                Some(&"vertex") => polygon.push(coordinates(1)?),
+                Some(&"endfacet") => {
+                    if polygon.len() < 3 {
+                        return Err(format!("Facet ending at line {} has {} vertices", number + 1, polygon.len()));
+                    }
+                    // some exporters write polygons, a fan splits them
+                    for i in 1..polygon.len() - 1 {
+                        facets.push([normal, polygon[0], polygon[i], polygon[i + 1]]);
+                    }
+                },
+                // solid, outer loop, endloop, endsolid and blank lines carry nothing
+                _ => {},
+            }
+        }
+        Ok(facets)
+    }
+}
+
+impl Model for StlModel {
+    /** Empty model if the file can't be loaded, `StlModel::load` tells why */
+    fn new(file_path: &Path) -> Self {
+        match StlModel::load(file_path) {
+            Result::Ok(model) => {
+                println!("Model {} load successfully", file_path.display());
+                model
+            },
+            Result::Err(err) => {
+                println!("Model loading error: {}", err);
+                StlModel {
+                    triangles: Vec::new(),
+                    normals: Vec::new(),
+                }
+            },
+        }
+    }
+    fn triangle_iter(&self) -> Iter<Triangle> {
+        self.triangles.iter()
+    }
+    fn triangle_normals(&self, face: usize) -> Option<Triangle> {
+        let normal = self.normals[face]?;
+        Some(Triangle {p0: normal, p1: normal, p2: normal})
+    }
+}
+
+#[test]
+fn load_ascii_and_binary() {
+    let ascii = "solid cube_corner
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 2
+      vertex 4 0 2
+      vertex 0 4 2
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 0 -4 0
+      vertex 1e0 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+endsolid cube_corner
+";
+    let mut binary = vec![0u8; BINARY_HEADER_SIZE];
+    // binary headers starting with "solid" must not confuse the loader
+    binary[..5].copy_from_slice(b"solid");
+    binary.extend_from_slice(&1u32.to_le_bytes());
+    for &value in [0.0f32, 0.0, 0.0, 0.0, 0.0, 2.0, 4.0, 0.0, 2.0, 0.0, 4.0, 2.0].iter() {
+        binary.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+    binary.extend_from_slice(&[0, 0]);
+
+    assert!(StlModel::is_binary(&binary));
+    assert!(!StlModel::is_binary(ascii.as_bytes()));
+    let facets = StlModel::read_ascii(ascii).unwrap();
+    assert_eq!(facets.len(), 3);
+    assert_eq!((facets[0][0].z, facets[2][3].y), (1.0, 1.0));
+    let facets = StlModel::read_binary(&binary).unwrap();
+    assert_eq!((facets[0][0].z, facets[0][3].y), (0.0, 4.0));
+
+    assert_eq!(StlModel::read_ascii("facet normal 0 0 1\nvertex 0 0 x\n").err(), Some("Bad number at line 2".to_string()));
+    assert_eq!(StlModel::read_ascii("facet\nvertex 0 0\n").err(), Some("Expected 3 coordinates at line 2".to_string()));
+    assert_eq!(StlModel::read_ascii("facet\nvertex 0 0 0\nendfacet\n").err(), Some("Facet ending at line 3 has 1 vertices".to_string()));
+    assert!(StlModel::read_binary(&binary[..binary.len() - 1]).is_err());
+    assert!(StlModel::from_bytes(&[0; 50]).is_err());
+    assert!(StlModel::load(Path::new("obj/missing.stl")).is_err());
+
+    let ascii = StlModel::from_bytes(ascii.as_bytes()).unwrap();
+    let binary = StlModel::from_bytes(&binary).unwrap();
+    // the quad is split in two
+    assert_eq!(ascii.triangle_iter().count(), 3);
+    assert_eq!(binary.triangle_iter().count(), 1);
+    let first = ascii.triangle(0);
+    assert_eq!((first.p1.x, first.p0.z), (1.0, 0.5));
+    assert_eq!(binary.triangle(0).p2.y, 1.0);
+    assert_eq!(ascii.triangle_normals(0).unwrap().p0.z, 1.0);
+    assert!(ascii.triangle_normals(1).is_none());
+    assert!(binary.triangle_normals(0).is_none());
+}
@@ -1,9 +1,60 @@
 use std::slice::Iter;
 use std::path::Path;
 
-use crate::geometry::Triangle;
+use crate::geometry::{Triangle, Vector3D};
+use crate::canvas_trait::RgbColor;
+use crate::model_obj::ObjModel;
+use crate::model_stl::StlModel;
+use crate::model_ply::PlyModel;
 
 pub trait Model {
     fn new(file_path: &Path) -> Self where Self: Sized;
     fn triangle_iter(&self) -> Iter<Triangle>;
-}
\ No newline at end of file
+    fn triangle(&self, face: usize) -> &Triangle {
+        &self.triangle_iter().as_slice()[face]
+    }
+    /** Texture coordinates of the face vertices, if the model has them */
+    fn triangle_uv(&self, _face: usize) -> Option<Triangle> {
+        None
+    }
+    /** Per-vertex normals of the face, if the model has them */
+    fn triangle_normals(&self, _face: usize) -> Option<Triangle> {
+        None
+    }
+    /** Per-vertex tangents and bitangents of the face, for tangent space normal maps */
+    fn triangle_tangents(&self, _face: usize) -> Option<(Triangle, Triangle)> {
+        None
+    }
+    /** Per-vertex linear RGB colors of the face, if the model has them */
+    fn triangle_colors(&self, _face: usize) -> Option<Triangle> {
+        None
+    }
+    /** Vertices drawn as a point cloud, models made of faces have none */
+    fn points(&self) -> &[Vector3D] {
+        &[]
+    }
+    /** Color of the point, if the model has vertex colors */
+    fn point_color(&self, _point: usize) -> Option<RgbColor> {
+        None
+    }
+}
+
+/** Largest absolute coordinate of the vertices, models are divided by it to fit into [-1, 1] */
+pub fn max_coordinate<'a, I: Iterator<Item = &'a Vector3D>>(vertices: I) -> f64 {
+    let max_abs = vertices.fold(0.0f64, |max_abs, vertex| max_abs.max(vertex.x.abs()).max(vertex.y.abs()).max(vertex.z.abs()));
+    if max_abs > 0.0 { max_abs } else { 1.0 }
+}
+
+/** Model file by its extension */
+pub fn load(file_path: &Path) -> Result<Box<Model>, String> {
+    if !file_path.is_file() {
+        return Err(format!("Model file {} not found", file_path.display()));
+    }
+    let extension = file_path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase());
+    match extension.as_ref().map(|extension| extension.as_str()) {
+        Some("obj") => Ok(Box::new(ObjModel::load(file_path)?)),
+        Some("stl") => Ok(Box::new(StlModel::load(file_path)?)),
+        Some("ply") => Ok(Box::new(PlyModel::load(file_path)?)),
+        _ => Err(format!("Unknown model format of {}, expected .obj, .stl or .ply", file_path.display())),
+    }
+}
@@ -0,0 +1,171 @@
+/** How filled triangles are anti-aliased */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Antialiasing {
+    None,
+    /** Shade `factor`x`factor` samples per pixel and downsample them with `filter` */
+    Supersample { factor: u32, filter: Filter },
+    /** Test coverage and depth at `samples` points per pixel, shade once per pixel */
+    Multisample { samples: u32 },
+}
+
+/** Downsampling filter of the resolve step */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    /** Average of the pixel's own samples */
+    Box,
+    /** Samples within one pixel from the center, weighted by distance */
+    Tent,
+}
+
+/** Most samples a pixel can have, 8x8 supersampling */
+pub const MAX_SAMPLES: usize = 64;
+const MAX_SUPERSAMPLE_FACTOR: u32 = 8;
+
+/** Standard rotated sample patterns, offsets from the pixel center in 1/16 of a pixel */
+const PATTERN_2: [(i32, i32); 2] = [(4, 4), (-4, -4)];
+const PATTERN_4: [(i32, i32); 4] = [(-2, -6), (6, -2), (-6, 2), (2, 6)];
+const PATTERN_8: [(i32, i32); 8] = [(1, -3), (-1, 3), (5, 1), (-3, -5), (-5, 5), (-7, -1), (3, 7), (7, -7)];
+
+/** Color and depth of every sample, canvas y axis goes up */
+pub struct SampleBuffer {
+    width: u32,
+    height: u32,
+    antialiasing: Antialiasing,
+    /** Sample positions inside the pixel, in [0, 1) */
+    positions: Vec<(f64, f64)>,
+    color: Vec<u32>,
+    depth: Vec<f64>,
+}
+
+impl SampleBuffer {
+    pub fn new(width: u32, height: u32, antialiasing: Antialiasing) -> Result<Self, String> {
+        let positions: Vec<(f64, f64)> = match antialiasing {
+            Antialiasing::None => return Err("No samples without antialiasing".to_string()),
+            Antialiasing::Supersample { factor, .. } => {
+                if factor < 1 || factor > MAX_SUPERSAMPLE_FACTOR {
+                    return Err(format!("Unsupported supersampling factor {}", factor));
+                }
+                let step = 1.0 / factor as f64;
+                (0..factor * factor)
+                    .map(|i| (((i % factor) as f64 + 0.5) * step, ((i / factor) as f64 + 0.5) * step))
+                    .collect()
+            },
+            Antialiasing::Multisample { samples } => {
+                let pattern: &[(i32, i32)] = match samples {
+                    2 => &PATTERN_2,
+                    4 => &PATTERN_4,
+                    8 => &PATTERN_8,
+                    _ => return Err(format!("Unsupported multisample count {}", samples)),
+                };
+                pattern.iter().map(|&(x, y)| (0.5 + x as f64 / 16.0, 0.5 + y as f64 / 16.0)).collect()
+            },
+        };
+        let count = (width * height) as usize * positions.len();
+        Ok(SampleBuffer {
+            width: width,
+            height: height,
+            antialiasing: antialiasing,
+            positions: positions,
+            color: vec![0; count],
+            depth: vec![std::f64::NEG_INFINITY; count],
+        })
+    }
+    pub fn get_antialiasing(&self) -> Antialiasing {
+        self.antialiasing
+    }
+    pub fn get_positions(&self) -> &[(f64, f64)] {
+        &self.positions
+    }
+    fn index(&self, x: i32, y: i32, sample: usize) -> Option<usize> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height || sample >= self.positions.len() {
+            return None;
+        }
+        Some((y as u32 * self.width + x as u32) as usize * self.positions.len() + sample)
+    }
+    /** Check if the sample point is closer than the one already drawn */
+    pub fn test(&self, x: i32, y: i32, sample: usize, z: f64) -> bool {
+        match self.index(x, y, sample) {
+            Some(index) => z > self.depth[index],
+            None => false,
+        }
+    }
+    pub fn get(&self, x: i32, y: i32, sample: usize) -> u32 {
+        self.index(x, y, sample).map_or(0, |index| self.color[index])
+    }
+    pub fn set(&mut self, x: i32, y: i32, sample: usize, color: u32, z: f64) {
+        if let Some(index) = self.index(x, y, sample) {
+            self.color[index] = color;
+            self.depth[index] = z;
+        }
+    }
+    /** Paint the whole pixel, depth is left as is */
+    pub fn fill(&mut self, x: i32, y: i32, color: u32) {
+        for sample in 0..self.positions.len() {
+            if let Some(index) = self.index(x, y, sample) {
+                self.color[index] = color;
+            }
+        }
+    }
+    pub fn clear(&mut self, color: u32) {
+        for sample in self.color.iter_mut() {
+            *sample = color;
+        }
+    }
+    pub fn clear_depth(&mut self) {
+        for z in self.depth.iter_mut() {
+            *z = std::f64::NEG_INFINITY;
+        }
+    }
+    /** Final color of the pixel */
+    pub fn resolve(&self, x: i32, y: i32) -> u32 {
+        let radius = match self.antialiasing {
+            Antialiasing::Supersample { filter: Filter::Tent, .. } => 1,
+            _ => 0,
+        };
+        // color is averaged premultiplied, so transparent samples don't darken it
+        let mut sum = [0.0; 3];
+        let mut alpha = 0.0;
+        let mut total = 0.0;
+        for n_y in y - radius..=y + radius {
+            for n_x in x - radius..=x + radius {
+                for (sample, &(s_x, s_y)) in self.positions.iter().enumerate() {
+                    let index = match self.index(n_x, n_y, sample) {
+                        Some(index) => index,
+                        None => continue,
+                    };
+                    let weight = if radius == 0 {
+                        1.0
+                    } else {
+                        let d_x = (n_x - x) as f64 + s_x - 0.5;
+                        let d_y = (n_y - y) as f64 + s_y - 0.5;
+                        (1.0 - d_x.abs()).max(0.0) * (1.0 - d_y.abs()).max(0.0)
+                    };
+                    let color = self.color[index];
+                    let sample_alpha = (color >> (8 * 3)) as f64 * weight;
+                    for channel in 0..3 {
+                        sum[channel] += ((color >> (8 * (2 - channel))) & 0xFF) as f64 * sample_alpha;
+                    }
+                    alpha += sample_alpha;
+                    total += weight;
+                }
+            }
+        }
+        if alpha == 0.0 {
+            return 0;
+        }
+        sum.iter().fold((alpha / total).round() as u32, |color, channel| (color << 8) | (channel / alpha).round() as u32)
+    }
+}
+
+#[test]
+fn sample_patterns() {
+    let ssaa = SampleBuffer::new(2, 2, Antialiasing::Supersample { factor: 2, filter: Filter::Box }).unwrap();
+    assert_eq!(ssaa.get_positions(), &[(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]);
+    assert!(SampleBuffer::new(2, 2, Antialiasing::Multisample { samples: 3 }).is_err());
+    let mut msaa = SampleBuffer::new(2, 2, Antialiasing::Multisample { samples: 4 }).unwrap();
+    msaa.set(0, 0, 0, 0xFFFFFFFF, 1.0);
+    msaa.set(0, 0, 1, 0xFFFFFFFF, 1.0);
+    assert!(!msaa.test(0, 0, 0, 0.5));
+    assert!(msaa.test(0, 0, 2, 0.5));
+    assert_eq!(msaa.resolve(0, 0), 0x80FFFFFF);
+}
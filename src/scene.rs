@@ -0,0 +1,673 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::canvas_trait::{Canvas, RgbColor};
+use crate::canvas_tga::OutputFormat;
+use crate::model_trait;
+use crate::geometry::{Vector3D, Matrix4};
+use crate::camera::{Camera, Projection};
+use crate::light::Light;
+use crate::material::Material;
+use crate::texture::Texture;
+use crate::shader::SurfaceMaps;
+use crate::cli::{self, Backend};
+use crate::hdr::ToneMapping;
+
+const SCENE_KEYS: &[&str] = &["models", "materials", "lights", "camera", "background", "output"];
+const MODEL_KEYS: &[&str] = &["file", "material", "shading", "translation", "rotation", "scale", "point_size"];
+const DEFAULT_POINT_SIZE: u32 = 2;
+const MATERIAL_KEYS: &[&str] = &["color", "ambient", "diffuse", "specular", "shininess", "blinn", "texture", "normal_map"];
+const CAMERA_KEYS: &[&str] = &["eye", "target", "up", "fov", "near", "far", "projection"];
+const OUTPUT_KEYS: &[&str] = &["path", "backend", "width", "height", "tone_mapping", "exposure"];
+
+/** How a scene model is drawn */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shading {
+    Wire,
+    Flat,
+    Random,
+    Gouraud,
+    Phong,
+    /** Diffuse texture of the material, required */
+    Textured,
+    /** Colors of the model vertices, the material color where it has none */
+    VertexColors,
+}
+
+pub struct SceneMaterial {
+    pub color: RgbColor,
+    pub material: Material,
+    pub texture: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+}
+
+impl Default for SceneMaterial {
+    fn default() -> Self {
+        SceneMaterial {
+            color: RgbColor::from_rgb(255, 255, 255),
+            material: Material::new(0.1, 0.8, 0.6, 32.0),
+            texture: None,
+            normal_map: None,
+        }
+    }
+}
+
+pub struct SceneModel {
+    pub file: PathBuf,
+    /** Name in `Scene::materials`, the default material when absent */
+    pub material: Option<String>,
+    pub shading: Shading,
+    pub transform: Matrix4,
+    /** Pixel size of points, for models that are point clouds */
+    pub point_size: u32,
+}
+
+pub struct Output {
+    pub backend: Backend,
+    pub path: PathBuf,
+    pub format: OutputFormat,
+    pub width: u32,
+    pub height: u32,
+    pub tone_mapping: ToneMapping,
+    pub exposure: f64,
+}
+
+impl Output {
+    /** Tone mapping of the canvas, call before rendering */
+    pub fn setup<C: Canvas>(&self, canvas: &mut C) -> Result<(), String> {
+        canvas.get_options().tone_mapping = self.tone_mapping;
+        canvas.get_options().exposure = self.exposure;
+        canvas.set_hdr(self.is_hdr())
+    }
+    /** Light is accumulated in HDR only for the operators that need it, plain clamping is exact in 8 bits */
+    pub fn is_hdr(&self) -> bool {
+        self.tone_mapping != ToneMapping::Clamp || self.exposure != 1.0
+    }
+}
+
+/**
+ * Models with their transforms and materials, lights, camera and output settings.
+ * Paths in the file are relative to the file itself.
+ */
+pub struct Scene {
+    pub models: Vec<SceneModel>,
+    pub materials: Vec<(String, SceneMaterial)>,
+    pub lights: Vec<Light>,
+    pub camera: Camera,
+    pub background: Option<RgbColor>,
+    pub output: Output,
+}
+
+impl Scene {
+    pub fn load(file_path: &Path) -> Result<Scene, String> {
+        let text = std::fs::read_to_string(file_path)
+            .map_err(|err| format!("Can't read {}: {}", file_path.display(), err))?;
+        let base = file_path.parent().unwrap_or(Path::new(""));
+        Scene::parse(&text, base).map_err(|err| format!("{}: {}", file_path.display(), err))
+    }
+    /** Scene from JSON text, relative paths are resolved against `base` */
+    pub fn parse(text: &str, base: &Path) -> Result<Scene, String> {
+        let root: Value = serde_json::from_str(text).map_err(|err| format!("JSON {}", err))?;
+        let root = object("scene", &root, SCENE_KEYS)?;
+        let mut materials = Vec::new();
+        if let Some(value) = field(root, "materials") {
+            for (name, value) in object("materials", value, &[])?.iter() {
+                materials.push((name.clone(), parse_material(&format!("materials.{}", name), value, base)?));
+            }
+        }
+        let mut models = Vec::new();
+        for (i, value) in array("models", field(root, "models").ok_or("scene: 'models' is required")?)?.iter().enumerate() {
+            let model = parse_model(&format!("models[{}]", i), value, base)?;
+            if let Some(ref name) = model.material {
+                if !materials.iter().any(|&(ref other, _)| other == name) {
+                    let defined: Vec<&str> = materials.iter().map(|&(ref name, _)| name.as_str()).collect();
+                    return Err(format!("models[{}].material: no material named '{}', defined: [{}]", i, name, defined.join(", ")));
+                }
+            }
+            models.push(model);
+        }
+        if models.is_empty() {
+            return Err("models: the scene has no models".to_string());
+        }
+        for (i, model) in models.iter().enumerate() {
+            let material = model.material.as_ref()
+                .and_then(|name| materials.iter().find(|&&(ref other, _)| other == name))
+                .map(|&(_, ref material)| material);
+            if model.shading == Shading::Textured && material.map_or(true, |material| material.texture.is_none()) {
+                return Err(format!("models[{}].shading: textured shading needs a material with a texture", i));
+            }
+        }
+        let lights = match field(root, "lights") {
+            Some(value) => array("lights", value)?.iter().enumerate()
+                .map(|(i, value)| parse_light(&format!("lights[{}]", i), value))
+                .collect::<Result<Vec<Light>, String>>()?,
+            None => vec![Light::directional(Vector3D {x: 0.0, y: 0.0, z: -1.0})],
+        };
+        let camera = match field(root, "camera") {
+            Some(value) => parse_camera("camera", value)?,
+            None => Camera::perspective(Vector3D {x: 0.0, y: 0.0, z: 3.0}, Vector3D {x: 0.0, y: 0.0, z: 0.0}),
+        };
+        let background = match field(root, "background") {
+            Some(value) => Some(color("background", value)?),
+            None => None,
+        };
+        let output = parse_output("output", field(root, "output"), base)?;
+        Ok(Scene {
+            models: models,
+            materials: materials,
+            lights: lights,
+            camera: camera,
+            background: background,
+            output: output,
+        })
+    }
+    /** Command line options as a scene: models side by side along x, centered on the origin */
+    pub fn from_options(options: &cli::Options) -> Result<Scene, String> {
+        let material = SceneMaterial {
+            color: options.color,
+            texture: options.texture.clone(),
+            normal_map: options.normal_map.clone(),
+            ..SceneMaterial::default()
+        };
+        for texture in material.texture.iter().chain(material.normal_map.iter()) {
+            if !texture.is_file() {
+                return Err(format!("Texture file {} not found", texture.display()));
+            }
+        }
+        let shading = match (options.mode, material.texture.is_some()) {
+            (cli::RenderMode::Wire, _) => Shading::Wire,
+            (cli::RenderMode::Flat, _) => Shading::Flat,
+            (cli::RenderMode::Random, _) => Shading::Random,
+            (cli::RenderMode::VertexColors, _) => Shading::VertexColors,
+            (cli::RenderMode::Lit, true) => Shading::Textured,
+            (cli::RenderMode::Lit, false) => Shading::Gouraud,
+        };
+        let count = options.models.len();
+        let scale = Matrix4::scale(Vector3D {x: options.scale, y: options.scale, z: options.scale});
+        let models = options.models.iter().enumerate().map(|(i, file)| SceneModel {
+            file: file.clone(),
+            material: Some("cli".to_string()),
+            shading: shading,
+            transform: Matrix4::translation(Vector3D {
+                           x: 2.0 * i as f64 - (count - 1) as f64,
+                           y: 0.0,
+                           z: 0.0
+                       }) * scale,
+            point_size: DEFAULT_POINT_SIZE,
+        }).collect();
+        Ok(Scene {
+            models: models,
+            materials: vec![("cli".to_string(), material)],
+            lights: vec![Light::directional(options.light)],
+            camera: options.camera,
+            background: options.background,
+            output: Output {
+                backend: options.backend,
+                path: options.output.clone(),
+                format: options.format,
+                width: options.width,
+                height: options.height,
+                tone_mapping: options.tone_mapping,
+                exposure: options.exposure,
+            },
+        })
+    }
+    pub fn get_material(&self, model: &SceneModel) -> Option<&SceneMaterial> {
+        let name = model.material.as_ref()?;
+        self.materials.iter().find(|&&(ref other, _)| other == name).map(|&(_, ref material)| material)
+    }
+    /** Draw every model through the canvas render modes, the canvas isn't output */
+    pub fn render<C: Canvas>(&self, canvas: &mut C) -> Result<(), String> {
+        if let Some(background) = self.background {
+            canvas.get_framebuffer_mut().clear(background.value());
+        }
+        let default = SceneMaterial::default();
+        for (i, model) in self.models.iter().enumerate() {
+            let material = self.get_material(model).unwrap_or(&default);
+            let load = |path: &Option<PathBuf>| -> Result<Option<Texture>, String> {
+                match *path {
+                    Some(ref path) => Texture::load(path)
+                        .map(Some)
+                        .map_err(|err| format!("models[{}]: texture {}: {}", i, path.display(), err)),
+                    None => Ok(None),
+                }
+            };
+            let normal_map = load(&material.normal_map)?;
+            let shape = model_trait::load(&model.file).map_err(|err| format!("models[{}]: {}", i, err))?;
+            let (transform, camera, lights) = (&model.transform, &self.camera, &self.lights[..]);
+            if shape.triangle_iter().len() == 0 && !shape.points().is_empty() {
+                // files without faces are point clouds whatever the shading
+                canvas.render_points(shape, transform, camera, material.color, model.point_size)
+                    .map_err(|err| format!("models[{}]: {}", i, err))?;
+                continue;
+            }
+            let maps = SurfaceMaps::new().with_normal_map(normal_map.as_ref());
+            let result = match model.shading {
+                Shading::Wire => canvas.render_wire(shape, transform, camera, material.color.value(), false),
+                Shading::Flat => canvas.render_poly_lightning(shape, transform, camera, lights, material.color, maps),
+                Shading::Random => canvas.render_poly_rnd_colored(shape, transform, camera),
+                Shading::Gouraud => canvas.render_gouraud(shape, transform, camera, lights, material.color, maps),
+                Shading::Phong => canvas.render_phong(shape, transform, camera, lights, material.color, &material.material, maps),
+                Shading::Textured => {
+                    let texture = load(&material.texture)?.unwrap();
+                    canvas.render_textured(shape, transform, camera, lights, &texture, maps)
+                },
+                Shading::VertexColors => canvas.render_vertex_colors(shape, transform, camera, material.color),
+            };
+            result.map_err(|err| format!("models[{}]: {}", i, err))?;
+        }
+        Ok(())
+    }
+}
+
+fn field<'a>(entries: &'a Map<String, Value>, key: &str) -> Option<&'a Value> {
+    entries.get(key)
+}
+
+/** Type name for error messages */
+fn kind(value: &Value) -> &'static str {
+    match *value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/** Object entries, checked against `keys` unless they are free names */
+fn object<'a>(path: &str, value: &'a Value, keys: &[&str]) -> Result<&'a Map<String, Value>, String> {
+    let entries = match *value {
+        Value::Object(ref entries) => entries,
+        ref other => return Err(format!("{}: expected an object, got {}", path, kind(other))),
+    };
+    if !keys.is_empty() {
+        for key in entries.keys() {
+            if !keys.contains(&key.as_str()) {
+                return Err(format!("{}: unknown key '{}', expected one of: {}", path, key, keys.join(", ")));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn array<'a>(path: &str, value: &'a Value) -> Result<&'a [Value], String> {
+    match *value {
+        Value::Array(ref items) => Ok(items),
+        ref other => Err(format!("{}: expected an array, got {}", path, kind(other))),
+    }
+}
+
+fn number(path: &str, value: &Value) -> Result<f64, String> {
+    match value.as_f64() {
+        Some(number) => Ok(number),
+        None => Err(format!("{}: expected a number, got {}", path, kind(value))),
+    }
+}
+
+fn string<'a>(path: &str, value: &'a Value) -> Result<&'a str, String> {
+    match *value {
+        Value::String(ref string) => Ok(string),
+        ref other => Err(format!("{}: expected a string, got {}", path, kind(other))),
+    }
+}
+
+fn vector(path: &str, value: &Value) -> Result<Vector3D, String> {
+    let items = array(path, value)?;
+    if items.len() != 3 {
+        return Err(format!("{}: expected [x, y, z], got {} items", path, items.len()));
+    }
+    Ok(Vector3D {
+        x: number(path, &items[0])?,
+        y: number(path, &items[1])?,
+        z: number(path, &items[2])?,
+    })
+}
+
+/** Vector that is used as a direction, so it can't be zero */
+fn direction(path: &str, value: &Value) -> Result<Vector3D, String> {
+    let direction = vector(path, value)?;
+    if direction.abs() < 1e-12 {
+        return Err(format!("{}: direction can't be zero", path));
+    }
+    Ok(direction)
+}
+
+fn color(path: &str, value: &Value) -> Result<RgbColor, String> {
+    cli::parse_color(string(path, value)?).map_err(|err| format!("{}: {}", path, err))
+}
+
+/** Optional number field within `min..=max` */
+fn bounded(path: &str, entries: &Map<String, Value>, key: &str, min: f64, max: f64) -> Result<Option<f64>, String> {
+    match field(entries, key) {
+        Some(value) => {
+            let path = format!("{}.{}", path, key);
+            let number = number(&path, value)?;
+            if number < min || number > max {
+                return Err(format!("{}: {} is out of [{}, {}]", path, number, min, max));
+            }
+            Ok(Some(number))
+        },
+        None => Ok(None),
+    }
+}
+
+/** Optional whole number field within `min..=max` */
+fn integer(path: &str, entries: &Map<String, Value>, key: &str, min: u32, max: u32) -> Result<Option<u32>, String> {
+    match field(entries, key) {
+        Some(value) => {
+            let path = format!("{}.{}", path, key);
+            let number = number(&path, value)?;
+            if number.fract() != 0.0 {
+                return Err(format!("{}: expected a whole number, got {}", path, number));
+            }
+            if number < min as f64 || number > max as f64 {
+                return Err(format!("{}: {} is out of [{}, {}]", path, number, min, max));
+            }
+            Ok(Some(number as u32))
+        },
+        None => Ok(None),
+    }
+}
+
+/** File path relative to the scene, the file has to exist */
+fn file(path: &str, value: &Value, base: &Path) -> Result<PathBuf, String> {
+    let file = base.join(string(path, value)?);
+    if !file.is_file() {
+        return Err(format!("{}: file {} not found", path, file.display()));
+    }
+    Ok(file)
+}
+
+fn parse_material(path: &str, value: &Value, base: &Path) -> Result<SceneMaterial, String> {
+    let entries = object(path, value, MATERIAL_KEYS)?;
+    let mut material = SceneMaterial::default();
+    if let Some(value) = field(entries, "color") {
+        material.color = color(&format!("{}.color", path), value)?;
+    }
+    let reflection = &mut material.material;
+    reflection.ambient = bounded(path, entries, "ambient", 0.0, 1.0)?.unwrap_or(reflection.ambient);
+    reflection.diffuse = bounded(path, entries, "diffuse", 0.0, 1.0)?.unwrap_or(reflection.diffuse);
+    reflection.specular = bounded(path, entries, "specular", 0.0, 1.0)?.unwrap_or(reflection.specular);
+    reflection.shininess = bounded(path, entries, "shininess", 1.0, 10000.0)?.unwrap_or(reflection.shininess);
+    if let Some(value) = field(entries, "blinn") {
+        reflection.blinn = match *value {
+            Value::Bool(blinn) => blinn,
+            ref other => return Err(format!("{}.blinn: expected a boolean, got {}", path, kind(other))),
+        };
+    }
+    if let Some(value) = field(entries, "texture") {
+        material.texture = Some(file(&format!("{}.texture", path), value, base)?);
+    }
+    if let Some(value) = field(entries, "normal_map") {
+        material.normal_map = Some(file(&format!("{}.normal_map", path), value, base)?);
+    }
+    Ok(material)
+}
+
+fn parse_model(path: &str, value: &Value, base: &Path) -> Result<SceneModel, String> {
+    let entries = object(path, value, MODEL_KEYS)?;
+    let file = file(&format!("{}.file", path), field(entries, "file").ok_or(format!("{}: 'file' is required", path))?, base)?;
+    let material = match field(entries, "material") {
+        Some(value) => Some(string(&format!("{}.material", path), value)?.to_string()),
+        None => None,
+    };
+    let shading = match field(entries, "shading") {
+        Some(value) => match string(&format!("{}.shading", path), value)? {
+            "wire" => Shading::Wire,
+            "flat" => Shading::Flat,
+            "random" => Shading::Random,
+            "gouraud" => Shading::Gouraud,
+            "phong" => Shading::Phong,
+            "textured" => Shading::Textured,
+            "vertex_colors" => Shading::VertexColors,
+            other => return Err(format!("{}.shading: unknown shading '{}', expected wire, flat, random, gouraud, phong, textured or vertex_colors", path, other)),
+        },
+        None => Shading::Phong,
+    };
+    let translation = match field(entries, "translation") {
+        Some(value) => vector(&format!("{}.translation", path), value)?,
+        None => Vector3D::default(),
+    };
+    // Euler angles in degrees, applied around x, then y, then z
+    let rotation = match field(entries, "rotation") {
+        Some(value) => vector(&format!("{}.rotation", path), value)?,
+        None => Vector3D::default(),
+    };
+    let scale = match field(entries, "scale") {
+        Some(value) if value.is_number() => {
+            let k = number(&format!("{}.scale", path), value)?;
+            Vector3D {x: k, y: k, z: k}
+        },
+        Some(value) => vector(&format!("{}.scale", path), value)?,
+        None => Vector3D {x: 1.0, y: 1.0, z: 1.0},
+    };
+    let axis = |x, y, z| Vector3D {x: x, y: y, z: z};
+    let transform = Matrix4::translation(translation)
+        * Matrix4::rotation(axis(0.0, 0.0, 1.0), rotation.z.to_radians())
+        * Matrix4::rotation(axis(0.0, 1.0, 0.0), rotation.y.to_radians())
+        * Matrix4::rotation(axis(1.0, 0.0, 0.0), rotation.x.to_radians())
+        * Matrix4::scale(scale);
+    let point_size = integer(path, entries, "point_size", 1, 64)?.unwrap_or(DEFAULT_POINT_SIZE);
+    Ok(SceneModel {
+        file: file,
+        material: material,
+        shading: shading,
+        transform: transform,
+        point_size: point_size,
+    })
+}
+
+fn parse_light(path: &str, value: &Value) -> Result<Light, String> {
+    let kind = match field(object(path, value, &[])?, "type") {
+        Some(kind) => string(&format!("{}.type", path), kind)?,
+        None => return Err(format!("{}: 'type' is required", path)),
+    };
+    let keys: &[&str] = match kind {
+        "directional" => &["type", "color", "intensity", "direction"],
+        "point" => &["type", "color", "intensity", "position"],
+        "spot" => &["type", "color", "intensity", "position", "direction", "inner_angle", "outer_angle"],
+        other => return Err(format!("{}.type: unknown light type '{}', expected directional, point or spot", path, other)),
+    };
+    let entries = object(path, value, keys)?;
+    let required = |key: &str| -> Result<&Value, String> {
+        field(entries, key).ok_or(format!("{}: '{}' is required for {} lights", path, key, kind))
+    };
+    let position = || -> Result<Vector3D, String> { vector(&format!("{}.position", path), required("position")?) };
+    let light_direction = || -> Result<Vector3D, String> { direction(&format!("{}.direction", path), required("direction")?) };
+    let mut light = match kind {
+        "directional" => Light::directional(light_direction()?),
+        "point" => Light::point(position()?),
+        _ => {
+            let outer = bounded(path, entries, "outer_angle", 0.0, 90.0)?.unwrap_or(30.0);
+            let inner = bounded(path, entries, "inner_angle", 0.0, outer)?.unwrap_or(outer * 0.8);
+            Light::spot(position()?, light_direction()?, inner.to_radians(), outer.to_radians())
+        },
+    };
+    if let Some(value) = field(entries, "color") {
+        light = light.with_color(color(&format!("{}.color", path), value)?);
+    }
+    if let Some(intensity) = bounded(path, entries, "intensity", 0.0, std::f64::MAX)? {
+        light = light.with_intensity(intensity);
+    }
+    Ok(light)
+}
+
+fn parse_camera(path: &str, value: &Value) -> Result<Camera, String> {
+    let entries = object(path, value, CAMERA_KEYS)?;
+    let point = |key: &str, default: Vector3D| -> Result<Vector3D, String> {
+        match field(entries, key) {
+            Some(value) => vector(&format!("{}.{}", path, key), value),
+            None => Ok(default),
+        }
+    };
+    let mut camera = Camera::perspective(point("eye", Vector3D {x: 0.0, y: 0.0, z: 3.0})?, point("target", Vector3D::default())?);
+    if let Some(value) = field(entries, "up") {
+        camera.up = direction(&format!("{}.up", path), value)?;
+    }
+    if (camera.eye - camera.target).abs() == 0.0 {
+        return Err(format!("{}: eye and target are the same point", path));
+    }
+    if !camera.has_valid_up() {
+        return Err(format!("{}.up: up is parallel to the view direction from eye to target", path));
+    }
+    if let Some(value) = field(entries, "fov") {
+        let path = format!("{}.fov", path);
+        camera.fov = cli::field_of_view(number(&path, value)?).map_err(|err| format!("{}: {}", path, err))?;
+    }
+    camera.near = bounded(path, entries, "near", 1e-6, std::f64::MAX)?.unwrap_or(camera.near);
+    camera.far = bounded(path, entries, "far", 1e-6, std::f64::MAX)?.unwrap_or(camera.far);
+    if camera.far <= camera.near {
+        return Err(format!("{}.far: far plane {} has to be beyond the near plane {}", path, camera.far, camera.near));
+    }
+    if let Some(value) = field(entries, "projection") {
+        camera.projection = match string(&format!("{}.projection", path), value)? {
+            "perspective" => Projection::Perspective,
+            "orthographic" => Projection::Orthographic,
+            other => return Err(format!("{}.projection: unknown projection '{}', expected perspective or orthographic", path, other)),
+        };
+    }
+    Ok(camera)
+}
+
+fn parse_output(path: &str, value: Option<&Value>, base: &Path) -> Result<Output, String> {
+    let defaults = cli::Options::default();
+    let empty = Map::new();
+    let entries = match value {
+        Some(value) => object(path, value, OUTPUT_KEYS)?,
+        None => &empty,
+    };
+    let file = match field(entries, "path") {
+        Some(value) => Some(base.join(string(&format!("{}.path", path), value)?)),
+        None => None,
+    };
+    let backend = match field(entries, "backend") {
+        Some(value) => match string(&format!("{}.backend", path), value)? {
+            "window" => Backend::Window,
+            "file" => Backend::File,
+            other => return Err(format!("{}.backend: unknown backend '{}', expected window or file", path, other)),
+        },
+        None if file.is_some() => Backend::File,
+        None => Backend::Window,
+    };
+    let file = file.unwrap_or(defaults.output);
+    let format = cli::output_format(&file).map_err(|err| format!("{}.path: {}", path, err))?;
+    let tone_mapping = match field(entries, "tone_mapping") {
+        Some(value) => {
+            let path = format!("{}.tone_mapping", path);
+            cli::tone_mapping(string(&path, value)?).map_err(|err| format!("{}: {}", path, err))?
+        },
+        None => defaults.tone_mapping,
+    };
+    Ok(Output {
+        backend: backend,
+        path: file,
+        format: format,
+        width: integer(path, entries, "width", 1, 65535)?.unwrap_or(defaults.width),
+        height: integer(path, entries, "height", 1, 65535)?.unwrap_or(defaults.height),
+        tone_mapping: tone_mapping,
+        exposure: bounded(path, entries, "exposure", 1e-6, 1e6)?.unwrap_or(defaults.exposure),
+    })
+}
+
+#[test]
+fn scene_validation() {
+    let scene = Scene::parse(r#"{
+        "materials": {"skin": {"color": "gray", "specular": 0.2}},
+        "models": [
+            {"file": "obj/african_head.obj", "material": "skin", "translation": [-1, 0, 0], "scale": 0.9},
+            {"file": "obj/african_head.obj", "shading": "wire", "rotation": [0, 90, 0]}
+        ],
+        "lights": [{"type": "point", "position": [0, 2, 2], "color": "FFE0C0", "intensity": 2}],
+        "camera": {"eye": [0, 0, 4], "fov": 60},
+        "output": {"path": "scene.png", "width": 320, "height": 160, "tone_mapping": "reinhard", "exposure": 1.5}
+    }"#, Path::new("")).unwrap();
+    assert_eq!(scene.models.len(), 2);
+    assert_eq!(scene.models[1].shading, Shading::Wire);
+    assert!((scene.get_material(&scene.models[0]).unwrap().material.specular - 0.2).abs() < 1e-12);
+    assert_eq!(scene.output.backend, Backend::File);
+    assert_eq!(scene.output.format, OutputFormat::Png);
+    assert_eq!((scene.output.width, scene.output.height), (320, 160));
+    assert_eq!((scene.output.tone_mapping, scene.output.exposure), (ToneMapping::Reinhard, 1.5));
+    // the second model is turned to the side
+    let side = scene.models[1].transform.transform_direction(Vector3D {x: 0.0, y: 0.0, z: 1.0});
+    assert!((side.x - 1.0).abs() < 1e-9);
+
+    let errors = [
+        (r#"{"models": [{"file": "obj/african_head.obj", "material": "skn"}], "materials": {"skin": {}}}"#,
+         "models[0].material: no material named 'skn', defined: [skin]"),
+        (r#"{"models": [{"file": "obj/african_head.obj", "colour": "red"}]}"#,
+         "models[0]: unknown key 'colour', expected one of: file, material, shading, translation, rotation, scale, point_size"),
+        (r#"{"models": [{"file": "obj/missing.obj"}]}"#, "models[0].file: file obj/missing.obj not found"),
+        (r#"{"models": [{"file": "obj/african_head.obj", "shading": "textured"}]}"#,
+         "models[0].shading: textured shading needs a material with a texture"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "lights": [{"type": "spot", "position": [0, 0, 1]}]}"#,
+         "lights[0]: 'direction' is required for spot lights"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "camera": {"fov": "wide"}}"#,
+         "camera.fov: expected a number, got string"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "camera": {"fov": 180}}"#,
+         "camera.fov: Field of view 180 is out of [1, 179] degrees"),
+        (r#"{"models": [], "output": {"path": "x.tga"}}"#, "models: the scene has no models"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "output": {"tone_mapping": "filmic"}}"#,
+         "output.tone_mapping: Unknown tone mapping 'filmic', expected clamp, reinhard, aces or exposure"),
+        (r#"{"model": []}"#, "scene: unknown key 'model', expected one of: models, materials, lights, camera, background, output"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "lights": [{"type": "directional", "direction": [0, 0, 0]}]}"#,
+         "lights[0].direction: direction can't be zero"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "camera": {"near": 1, "far": 1}}"#,
+         "camera.far: far plane 1 has to be beyond the near plane 1"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "camera": {"eye": [0, 3, 0]}}"#,
+         "camera.up: up is parallel to the view direction from eye to target"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "camera": {"up": [0, 0, 0]}}"#,
+         "camera.up: direction can't be zero"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "output": {"width": 320.5}}"#,
+         "output.width: expected a whole number, got 320.5"),
+        (r#"{"models": [{"file": "obj/african_head.obj", "point_size": 1.5}]}"#,
+         "models[0].point_size: expected a whole number, got 1.5"),
+        (r#"{"models": [{"file": "obj/african_head.obj"}], "camera": {"eye": [0, 0, 3],}}"#,
+         "JSON trailing comma at line 1 column 76"),
+    ];
+    for &(text, expected) in errors.iter() {
+        assert_eq!(Scene::parse(text, Path::new("")).err(), Some(expected.to_string()));
+    }
+    // paths in scene files are relative to the file
+    let example = Scene::load(Path::new("scenes/heads.json")).unwrap();
+    assert_eq!(example.models[0].file, Path::new("scenes/../obj/african_head.obj"));
+}
+
+#[test]
+fn output_setup() {
+    use crate::canvas_memory::MemoryCanvas;
+    use crate::multisample::Antialiasing;
+    let text = r#"{"models": [{"file": "obj/african_head.obj"}], "background": "white"}"#;
+    let scene = Scene::parse(text, Path::new("")).unwrap();
+    let mut canvas: MemoryCanvas = Canvas::new(32, 32);
+    scene.output.setup(&mut canvas).unwrap();
+    // clamping by default keeps exact colors and leaves anti-aliasing available
+    assert!(!canvas.get_framebuffer().is_hdr());
+    canvas.set_antialiasing(Antialiasing::Multisample {samples: 4}).unwrap();
+    scene.render(&mut canvas).unwrap();
+    canvas.out().unwrap();
+    assert_eq!(canvas.get(0, 0), Ok(0xFFFFFFFF));
+
+    let text = r#"{"models": [{"file": "obj/african_head.obj"}], "output": {"tone_mapping": "aces"}}"#;
+    let scene = Scene::parse(text, Path::new("")).unwrap();
+    let mut canvas: MemoryCanvas = Canvas::new(32, 32);
+    scene.output.setup(&mut canvas).unwrap();
+    assert!(canvas.get_framebuffer().is_hdr());
+}
+
+#[test]
+fn textured_scene() {
+    use crate::canvas_memory::MemoryCanvas;
+    let scene = Scene::load(Path::new("scenes/textured.json")).unwrap();
+    assert_eq!(scene.models[0].shading, Shading::Textured);
+    let mut canvas: MemoryCanvas = Canvas::new(64, 64);
+    scene.render(&mut canvas).unwrap();
+    // a white untextured material would give only grays
+    let tinted = canvas.get_framebuffer().pixels().iter()
+        .filter(|&&pixel| (pixel >> (8*2)) & 0xFF != pixel & 0xFF)
+        .count();
+    assert!(tinted > 100, "{} tinted pixels", tinted);
+}
@@ -0,0 +1,530 @@
+use crate::geometry::*;
+use crate::model_trait::Model;
+use crate::canvas_trait::{RgbColor, Ergbcolor};
+use crate::camera::Camera;
+use crate::material::Material;
+use crate::texture::Texture;
+use crate::light::Light;
+use crate::shadow::{ShadowMap, Visibility};
+
+extern crate rand;
+use rand::Rng;
+
+/**
+ * Programmable stages of `Canvas::draw`.
+ * Every vertex is shaded on its own, anything the fragment stage needs goes through the varyings.
+ */
+pub trait Shader {
+    /** Values the vertex stage passes to the fragment stage */
+    type Varying: Varying;
+    /** Clip space position of the `nth` vertex of the face and its varyings */
+    fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, Self::Varying);
+    /** Pixel color from the varyings interpolated at the pixel, None discards the pixel */
+    fn fragment(&mut self, varying: Self::Varying) -> Option<RgbColor>;
+}
+
+/** Per-vertex value interpolated across the face with perspective correct barycentric weights */
+pub trait Varying: Copy {
+    fn interpolate(values: &[Self; 3], bc: Vector3D) -> Self;
+}
+
+impl Varying for () {
+    fn interpolate(_values: &[Self; 3], _bc: Vector3D) -> Self {}
+}
+
+impl Varying for f64 {
+    fn interpolate(values: &[Self; 3], bc: Vector3D) -> Self {
+        values[0] * bc.x + values[1] * bc.y + values[2] * bc.z
+    }
+}
+
+impl Varying for Vector3D {
+    fn interpolate(values: &[Self; 3], bc: Vector3D) -> Self {
+        values[0] * bc.x + values[1] * bc.y + values[2] * bc.z
+    }
+}
+
+/** Tuples interpolate every member on its own */
+macro_rules! tuple_varying {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Varying),+> Varying for ($($name,)+) {
+            fn interpolate(values: &[Self; 3], bc: Vector3D) -> Self {
+                ($($name::interpolate(&[values[0].$index, values[1].$index, values[2].$index], bc),)+)
+            }
+        }
+    };
+}
+
+tuple_varying!(A 0, B 1);
+tuple_varying!(A 0, B 1, C 2);
+tuple_varying!(A 0, B 1, C 2, D 3);
+tuple_varying!(A 0, B 1, C 2, D 3, E 4);
+tuple_varying!(A 0, B 1, C 2, D 3, E 4, F 5);
+tuple_varying!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+
+/** Transforms and lights shared by the built-in shaders */
+#[derive(Clone)]
+pub struct Uniforms {
+    /** Model to world transform */
+    pub transform: Matrix4,
+    pub modelview: Matrix4,
+    pub projection: Matrix4,
+    pub normal_matrix: Matrix3,
+    /** Lights in camera space */
+    pub lights: Vec<Light>,
+    pub camera: Camera,
+}
+
+impl Uniforms {
+    /** `lights` are given in world space */
+    pub fn new(transform: &Matrix4, camera: &Camera, projection: &Matrix4, lights: &[Light]) -> Self {
+        let modelview = camera.view_matrix() * *transform;
+        Uniforms {
+            transform: *transform,
+            modelview: modelview,
+            projection: *projection,
+            normal_matrix: Matrix3::normal_matrix(&modelview),
+            lights: lights.iter().map(|light| light.transform(&camera.view_matrix())).collect(),
+            camera: *camera,
+        }
+    }
+    /**
+     * Sum of all lights reflected at the camera space point with outward unit `normal`.
+     * `visibility` scales the one light a shadow map was rendered for, ambient and other lights stay unshadowed.
+     * Returns (ambient + diffuse, specular) rgb weights.
+     */
+    pub fn shade(&self, point: Vector3D, normal: Vector3D, material: &Material, visibility: Option<Visibility>) -> (Vector3D, Vector3D) {
+        let view = self.camera.view_direction(point);
+        let mut diffuse = Vector3D {x: material.ambient, y: material.ambient, z: material.ambient};
+        let mut specular = Vector3D::default();
+        for (i, light) in self.lights.iter().enumerate() {
+            let (to_light, mut radiance) = light.illuminate(point);
+            if let Some(visibility) = visibility {
+                if visibility.light == i {
+                    radiance = radiance * visibility.lit;
+                }
+            }
+            let (d, s) = material.reflect(normal, to_light, view);
+            diffuse = diffuse + radiance * d;
+            specular = specular + radiance * s;
+        }
+        (diffuse, specular)
+    }
+    /** Lambert lighting of the point, as rgb weights */
+    pub fn diffuse(&self, point: Vector3D, normal: Vector3D, visibility: Option<Visibility>) -> Vector3D {
+        self.shade(point, normal, &Material::lambert(), visibility).0
+    }
+    /** Camera space and clip space positions of the vertex */
+    pub fn transform(&self, position: Vector3D) -> (Vector3D, Vector4D) {
+        let eye = self.modelview.transform_point(position);
+        (eye, self.projection * Vector4D::from_point(eye))
+    }
+    /** Camera space center and outward unit normal of the face */
+    pub fn face(&self, model: &Model, face: usize) -> (Vector3D, Vector3D) {
+        let eye = model.triangle(face).transform(&self.modelview);
+        // face normal points inwards
+        (eye.interpolate(Vector3D {x: 1.0, y: 1.0, z: 1.0} / 3.0), -eye.normal().normalize())
+    }
+    /** Outward camera space unit normal of the `nth` vertex of the face, the face normal if the model has none */
+    pub fn normal(&self, model: &Model, face: usize, nth: usize) -> Vector3D {
+        match model.triangle_normals(face) {
+            Some(normals) => (self.normal_matrix * normals.vertex(nth)).normalize(),
+            None => self.face(model, face).1,
+        }
+    }
+}
+
+/**
+ * Texture coordinates, camera space tangent and bitangent of a vertex for normal mapping.
+ * All zero where the face has no texture mapping.
+ */
+pub type TangentFrame = (Vector3D, Vector3D, Vector3D);
+
+/** Tangent space normal map, perturbs interpolated shading normals */
+pub struct NormalMapping<'a> {
+    map: &'a Texture,
+}
+
+impl<'a> NormalMapping<'a> {
+    pub fn new(map: &'a Texture) -> Self {
+        NormalMapping {
+            map: map,
+        }
+    }
+    /** Varying of the `nth` vertex of the face */
+    pub fn vertex(&self, uniforms: &Uniforms, model: &Model, face: usize, nth: usize) -> TangentFrame {
+        match (model.triangle_uv(face), model.triangle_tangents(face)) {
+            (Some(uv), Some((tangents, bitangents))) => (
+                uv.vertex(nth),
+                uniforms.modelview.transform_direction(tangents.vertex(nth)),
+                uniforms.modelview.transform_direction(bitangents.vertex(nth)),
+            ),
+            _ => (Vector3D::default(), Vector3D::default(), Vector3D::default()),
+        }
+    }
+    /** Outward unit `normal` at the point turned by the map */
+    pub fn perturb(&self, normal: Vector3D, frame: TangentFrame) -> Vector3D {
+        let (uv, tangent, bitangent) = frame;
+        // Gram-Schmidt keeps the interpolated basis orthogonal to the normal
+        let tangent = tangent - normal * normal.scalar(tangent);
+        if tangent.abs() < 1e-9 {
+            return normal; // face without texture mapping
+        }
+        let tangent = tangent.normalize();
+        let mut mapped_bitangent = normal * tangent;
+        if mapped_bitangent.scalar(bitangent) < 0.0 {
+            mapped_bitangent = -mapped_bitangent; // mirrored texture mapping
+        }
+        let m = self.map.sample_normal(uv);
+        (tangent * m.x + mapped_bitangent * m.y + normal * m.z).normalize()
+    }
+}
+
+/**
+ * Lambert lighting of a vertex with the shadowed light passing and blocked.
+ * Lighting is affine in the visibility, so fragments blend the pair by the interpolated one.
+ */
+pub type LightRange = (Vector3D, Vector3D);
+
+/** Normal map and shadow map of the lit shaders, lookups are skipped for the missing ones */
+#[derive(Default)]
+pub struct SurfaceMaps<'a> {
+    normal_map: Option<NormalMapping<'a>>,
+    shadow: Option<&'a ShadowMap>,
+}
+
+impl<'a> SurfaceMaps<'a> {
+    pub fn new() -> Self {
+        SurfaceMaps::default()
+    }
+    pub fn with_normal_map(mut self, map: Option<&'a Texture>) -> Self {
+        self.normal_map = map.map(NormalMapping::new);
+        self
+    }
+    /** Shadow map of one of the uniform lights, rendered for the same scene */
+    pub fn with_shadow(mut self, shadow: Option<&'a ShadowMap>) -> Self {
+        self.shadow = shadow;
+        self
+    }
+    pub fn has_normal_map(&self) -> bool {
+        self.normal_map.is_some()
+    }
+    /** Tangent frame of the `nth` vertex of the face, zero without a normal map */
+    pub fn tangent_frame(&self, uniforms: &Uniforms, model: &Model, face: usize, nth: usize) -> TangentFrame {
+        match self.normal_map {
+            Some(ref normal_map) => normal_map.vertex(uniforms, model, face, nth),
+            None => (Vector3D::default(), Vector3D::default(), Vector3D::default()),
+        }
+    }
+    /** Outward unit `normal` turned by the normal map, unchanged without one */
+    pub fn perturb(&self, normal: Vector3D, frame: TangentFrame) -> Vector3D {
+        match self.normal_map {
+            Some(ref normal_map) => normal_map.perturb(normal, frame),
+            None => normal,
+        }
+    }
+    /** Light range of the point, both ends equal without a shadow map */
+    pub fn light_range(&self, uniforms: &Uniforms, point: Vector3D, normal: Vector3D) -> LightRange {
+        let lit = uniforms.diffuse(point, normal, None);
+        match self.shadow {
+            Some(shadow) => (lit, uniforms.diffuse(point, normal, Some(shadow.blocked()))),
+            None => (lit, lit),
+        }
+    }
+    /** Shadow map screen position of the `nth` vertex of the face, zero without a shadow map */
+    pub fn shadow_position(&self, uniforms: &Uniforms, model: &Model, face: usize, nth: usize) -> Vector3D {
+        match self.shadow {
+            Some(shadow) => shadow.to_screen(uniforms.transform.transform_point(model.triangle(face).vertex(nth))),
+            None => Vector3D::default(),
+        }
+    }
+    /** Light of the fragment at the shadow map screen position, picked from the range of its vertices */
+    pub fn blend(&self, range: LightRange, screen: Vector3D) -> Vector3D {
+        match self.shadow {
+            // orthographic light keeps shadow map coordinates affine in world space
+            Some(shadow) => range.1 + (range.0 - range.1) * shadow.visibility(screen),
+            None => range.0,
+        }
+    }
+    /** Visibility of the shadowed light at the shadow map screen position */
+    pub fn visibility(&self, screen: Vector3D) -> Option<Visibility> {
+        self.shadow.map(|shadow| shadow.light_visibility(screen))
+    }
+}
+
+/** Face lit with a single intensity, per pixel with a normal map */
+pub struct FlatShader<'a> {
+    uniforms: Uniforms,
+    color: RgbColor,
+    maps: SurfaceMaps<'a>,
+}
+
+impl<'a> FlatShader<'a> {
+    pub fn new(uniforms: Uniforms, color: RgbColor, maps: SurfaceMaps<'a>) -> Self {
+        FlatShader {
+            uniforms: uniforms,
+            color: color,
+            maps: maps,
+        }
+    }
+}
+
+impl<'a> Shader for FlatShader<'a> {
+    /** Camera space position, face normal, face light range, tangent frame and shadow map position */
+    type Varying = (Vector3D, Vector3D, LightRange, TangentFrame, Vector3D);
+    fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, Self::Varying) {
+        let (eye, clip) = self.uniforms.transform(model.triangle(face).vertex(nth));
+        // every vertex gets the light of the face center, so it is constant across the face
+        let (center, normal) = self.uniforms.face(model, face);
+        let range = self.maps.light_range(&self.uniforms, center, normal);
+        let frame = self.maps.tangent_frame(&self.uniforms, model, face, nth);
+        (clip, (eye, normal, range, frame, self.maps.shadow_position(&self.uniforms, model, face, nth)))
+    }
+    fn fragment(&mut self, varying: Self::Varying) -> Option<RgbColor> {
+        let (eye, normal, range, frame, screen) = varying;
+        if self.maps.has_normal_map() {
+            let normal = self.maps.perturb(normal.normalize(), frame);
+            return Some(self.color * self.uniforms.diffuse(eye, normal, self.maps.visibility(screen)));
+        }
+        Some(self.color * self.maps.blend(range, screen))
+    }
+}
+
+/** Lighting computed for vertices and interpolated across the face, per pixel with a normal map */
+pub struct GouraudShader<'a> {
+    uniforms: Uniforms,
+    color: RgbColor,
+    maps: SurfaceMaps<'a>,
+}
+
+impl<'a> GouraudShader<'a> {
+    pub fn new(uniforms: Uniforms, color: RgbColor, maps: SurfaceMaps<'a>) -> Self {
+        GouraudShader {
+            uniforms: uniforms,
+            color: color,
+            maps: maps,
+        }
+    }
+}
+
+impl<'a> Shader for GouraudShader<'a> {
+    /** Camera space position, normal, light range, tangent frame and shadow map position */
+    type Varying = (Vector3D, Vector3D, LightRange, TangentFrame, Vector3D);
+    fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, Self::Varying) {
+        let (eye, clip) = self.uniforms.transform(model.triangle(face).vertex(nth));
+        let normal = self.uniforms.normal(model, face, nth);
+        let range = self.maps.light_range(&self.uniforms, eye, normal);
+        let frame = self.maps.tangent_frame(&self.uniforms, model, face, nth);
+        (clip, (eye, normal, range, frame, self.maps.shadow_position(&self.uniforms, model, face, nth)))
+    }
+    fn fragment(&mut self, varying: Self::Varying) -> Option<RgbColor> {
+        let (eye, normal, range, frame, screen) = varying;
+        if self.maps.has_normal_map() {
+            let normal = self.maps.perturb(normal.normalize(), frame);
+            return Some(self.color * self.uniforms.diffuse(eye, normal, self.maps.visibility(screen)));
+        }
+        Some(self.color * self.maps.blend(range, screen))
+    }
+}
+
+/** Phong lighting with normals interpolated for every pixel */
+pub struct PhongShader<'a> {
+    uniforms: Uniforms,
+    color: RgbColor,
+    material: Material,
+    maps: SurfaceMaps<'a>,
+}
+
+impl<'a> PhongShader<'a> {
+    pub fn new(uniforms: Uniforms, color: RgbColor, material: Material, maps: SurfaceMaps<'a>) -> Self {
+        PhongShader {
+            uniforms: uniforms,
+            color: color,
+            material: material,
+            maps: maps,
+        }
+    }
+}
+
+impl<'a> Shader for PhongShader<'a> {
+    /** Camera space position, normal, tangent frame and shadow map position */
+    type Varying = (Vector3D, Vector3D, TangentFrame, Vector3D);
+    fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, Self::Varying) {
+        let (eye, clip) = self.uniforms.transform(model.triangle(face).vertex(nth));
+        let normal = self.uniforms.normal(model, face, nth);
+        let frame = self.maps.tangent_frame(&self.uniforms, model, face, nth);
+        (clip, (eye, normal, frame, self.maps.shadow_position(&self.uniforms, model, face, nth)))
+    }
+    fn fragment(&mut self, varying: Self::Varying) -> Option<RgbColor> {
+        let (eye, normal, frame, screen) = varying;
+        let normal = self.maps.perturb(normal.normalize(), frame);
+        let (diffuse, specular) = self.uniforms.shade(eye, normal, &self.material, self.maps.visibility(screen));
+        Some(self.color * diffuse + RgbColor::new(Ergbcolor::WHITE) * specular)
+    }
+}
+
+/** Flat lit texture, faces without texture coordinates are discarded. Lit per pixel with a normal map */
+pub struct TextureShader<'a> {
+    uniforms: Uniforms,
+    texture: &'a Texture,
+    maps: SurfaceMaps<'a>,
+}
+
+impl<'a> TextureShader<'a> {
+    pub fn new(uniforms: Uniforms, texture: &'a Texture, maps: SurfaceMaps<'a>) -> Self {
+        TextureShader {
+            uniforms: uniforms,
+            texture: texture,
+            maps: maps,
+        }
+    }
+}
+
+impl<'a> Shader for TextureShader<'a> {
+    /** Camera space position, 1 if the face is mapped, texture coordinates, normal, face light range, tangent frame and shadow map position */
+    type Varying = (Vector3D, f64, Vector3D, Vector3D, LightRange, TangentFrame, Vector3D);
+    fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, Self::Varying) {
+        let (eye, clip) = self.uniforms.transform(model.triangle(face).vertex(nth));
+        let (mapped, uv) = match model.triangle_uv(face) {
+            Some(uv) => (1.0, uv.vertex(nth)),
+            None => (0.0, Vector3D::default()),
+        };
+        let (center, face_normal) = self.uniforms.face(model, face);
+        let range = self.maps.light_range(&self.uniforms, center, face_normal);
+        // normal maps perturb smooth normals, without one only the face light is used
+        let normal = if self.maps.has_normal_map() { self.uniforms.normal(model, face, nth) } else { face_normal };
+        let frame = self.maps.tangent_frame(&self.uniforms, model, face, nth);
+        (clip, (eye, mapped, uv, normal, range, frame, self.maps.shadow_position(&self.uniforms, model, face, nth)))
+    }
+    fn fragment(&mut self, varying: Self::Varying) -> Option<RgbColor> {
+        let (eye, mapped, uv, normal, range, frame, screen) = varying;
+        if mapped < 0.5 {
+            return None;
+        }
+        let intensity = if self.maps.has_normal_map() {
+            let normal = self.maps.perturb(normal.normalize(), frame);
+            self.uniforms.diffuse(eye, normal, self.maps.visibility(screen))
+        } else {
+            self.maps.blend(range, screen)
+        };
+        Some(self.texture.sample(uv) * intensity)
+    }
+}
+
+/** Vertex colors of the model interpolated across faces, unlit, `color` for models without them */
+pub struct VertexColorShader {
+    uniforms: Uniforms,
+    color: RgbColor,
+}
+
+impl VertexColorShader {
+    pub fn new(uniforms: Uniforms, color: RgbColor) -> Self {
+        VertexColorShader {
+            uniforms: uniforms,
+            color: color,
+        }
+    }
+}
+
+impl Shader for VertexColorShader {
+    /** Linear color */
+    type Varying = Vector3D;
+    fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, Self::Varying) {
+        let color = match model.triangle_colors(face) {
+            Some(colors) => colors.vertex(nth),
+            None => self.color.to_linear(),
+        };
+        (self.uniforms.transform(model.triangle(face).vertex(nth)).1, color)
+    }
+    fn fragment(&mut self, varying: Self::Varying) -> Option<RgbColor> {
+        Some(RgbColor::from_linear(varying))
+    }
+}
+
+/** Every face gets its own random color */
+pub struct RandomColorShader {
+    uniforms: Uniforms,
+    rng: rand::ThreadRng,
+    /** Linear colors of the faces seen so far */
+    colors: Vec<Vector3D>,
+}
+
+impl RandomColorShader {
+    pub fn new(uniforms: Uniforms) -> Self {
+        RandomColorShader {
+            uniforms: uniforms,
+            rng: rand::thread_rng(),
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl Shader for RandomColorShader {
+    /** Linear color */
+    type Varying = Vector3D;
+    fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, Self::Varying) {
+        while self.colors.len() <= face {
+            let color = RgbColor::from_rgb(self.rng.gen(), self.rng.gen(), self.rng.gen());
+            self.colors.push(color.to_linear());
+        }
+        (self.uniforms.transform(model.triangle(face).vertex(nth)).1, self.colors[face])
+    }
+    fn fragment(&mut self, varying: Self::Varying) -> Option<RgbColor> {
+        Some(RgbColor::from_linear(varying))
+    }
+}
+
+#[test]
+fn discarding_shader() {
+    use std::path::Path;
+    use crate::canvas_trait::Canvas;
+    use crate::canvas_tga::TgaCanvas;
+    use crate::model_obj::ObjModel;
+
+    struct DiscardShader {
+        uniforms: Uniforms,
+        fragments: usize,
+    }
+    impl Shader for DiscardShader {
+        type Varying = ();
+        fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, ()) {
+            (self.uniforms.transform(model.triangle(face).vertex(nth)).1, ())
+        }
+        fn fragment(&mut self, _varying: ()) -> Option<RgbColor> {
+            self.fragments += 1;
+            None
+        }
+    }
+
+    let model: ObjModel = Model::new(Path::new("obj/african_head.obj"));
+    let mut canvas: TgaCanvas = Canvas::new(64, 64);
+    let camera = Camera::perspective(Vector3D {x: 0.0, y: 0.0, z: 3.0}, Vector3D {x: 0.0, y: 0.0, z: 0.0});
+    let mut shader = DiscardShader {
+        uniforms: canvas.get_uniforms(&Matrix4::identity(), &camera, &[]),
+        fragments: 0,
+    };
+    canvas.draw(&model, &mut shader).unwrap();
+    println!("Fragments: {}", shader.fragments);
+    assert!(shader.fragments > 0);
+    // discarded fragments don't touch the depth buffer
+    assert!(canvas.get_zbuffer().get(32, 32).unwrap().is_infinite());
+}
+
+#[test]
+fn flat_normal_map() {
+    use image::{RgbImage, Rgb};
+    use std::path::Path;
+    use crate::model_obj::ObjModel;
+
+    // map pointing straight out of the surface keeps the normal
+    let flat = Texture::from_image(RgbImage::from_pixel(4, 4, Rgb { data: [128, 128, 255] }));
+    let model: ObjModel = Model::new(Path::new("obj/african_head.obj"));
+    let camera = Camera::perspective(Vector3D {x: 0.0, y: 0.0, z: 3.0}, Vector3D {x: 0.0, y: 0.0, z: 0.0});
+    let uniforms = Uniforms::new(&Matrix4::identity(), &camera, &camera.projection_matrix(1.0), &[]);
+    let mapping = NormalMapping::new(&flat);
+    let frames = [mapping.vertex(&uniforms, &model, 0, 0), mapping.vertex(&uniforms, &model, 0, 1), mapping.vertex(&uniforms, &model, 0, 2)];
+    let normals = [uniforms.normal(&model, 0, 0), uniforms.normal(&model, 0, 1), uniforms.normal(&model, 0, 2)];
+    let bc = Vector3D {x: 0.2, y: 0.3, z: 0.5};
+    let normal = Vector3D::interpolate(&normals, bc).normalize();
+    assert!((mapping.perturb(normal, TangentFrame::interpolate(&frames, bc)) - normal).abs() < 0.01);
+}
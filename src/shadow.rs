@@ -0,0 +1,189 @@
+use crate::geometry::*;
+use crate::model_trait::Model;
+use crate::canvas_trait::{Canvas, RgbColor, Ergbcolor};
+use crate::canvas_memory::MemoryCanvas;
+use crate::shader::Shader;
+use crate::light::{Light, LightKind};
+
+/** Part of one light reaching a point, `Uniforms::shade` scales only that light by it */
+#[derive(Clone, Copy, Debug)]
+pub struct Visibility {
+    /** Index of the light in the uniform lights */
+    pub light: usize,
+    /** 0 when the light is fully blocked, 1 when it passes */
+    pub lit: f64,
+}
+
+/**
+ * Depth of the scene seen from one directional light of a light list.
+ * The light looks at a world point, everything within `radius` of it is covered.
+ */
+pub struct ShadowMap {
+    /** Depth pass target, only its depth buffer is used */
+    canvas: MemoryCanvas,
+    /** Index of the shadowed light */
+    light: usize,
+    direction: Vector3D,
+    /** World space to light clip space */
+    clip_transform: Matrix4,
+    /** Depth tolerance against self-shadowing, in shadow map depth units */
+    pub bias: f64,
+    /** Percentage-closer filtering over (2 * pcf_radius + 1)^2 texels, 0 gives hard edges */
+    pub pcf_radius: i32,
+    /** Part of the light blocked in shadow */
+    pub strength: f64,
+}
+
+impl ShadowMap {
+    /** Shadows of `lights[light]`, which has to be directional, over the sphere of `radius` around `center` */
+    pub fn new(size: u32, lights: &[Light], light: usize, center: Vector3D, radius: f64) -> Result<Self, String> {
+        if size == 0 {
+            return Err("Shadow map size has to be positive".to_string());
+        }
+        if !radius.is_finite() || radius <= 0.0 {
+            return Err(format!("Shadow map radius has to be positive, got {}", radius));
+        }
+        let direction = match lights.get(light).map(|light| light.kind) {
+            Some(LightKind::Directional { direction }) => direction.normalize(),
+            Some(_) => return Err(format!("Light {} casts no shadow map, only directional lights do", light)),
+            None => return Err(format!("No light {} to shadow among {} lights", light, lights.len())),
+        };
+        let up = if direction.x.abs() < 1e-6 && direction.z.abs() < 1e-6 {
+            Vector3D {x: 1.0, y: 0.0, z: 0.0}
+        } else {
+            Vector3D {x: 0.0, y: 1.0, z: 0.0}
+        };
+        let eye = center - direction * (2.0 * radius);
+        let view = Matrix4::look_at(eye, center, up);
+        let projection = Matrix4::orthographic(-radius, radius, -radius, radius, radius, 3.0 * radius);
+        let mut canvas: MemoryCanvas = Canvas::new(size, size);
+        // closed or not, every face blocks the light
+        canvas.get_options().cull_back_faces = false;
+        Ok(ShadowMap {
+            canvas: canvas,
+            light: light,
+            clip_transform: projection * view,
+            direction: direction,
+            bias: 0.005,
+            pcf_radius: 1,
+            strength: 1.0,
+        })
+    }
+    pub fn get_light(&self) -> usize {
+        self.light
+    }
+    pub fn get_direction(&self) -> Vector3D {
+        self.direction
+    }
+    /** Depth pass, every shadow caster is rendered once before the shaded pass */
+    pub fn render(&mut self, model: &Model, transform: &Matrix4) -> Result<(), String> {
+        let mut shader = DepthShader {
+            clip_transform: self.clip_transform * *transform,
+        };
+        self.canvas.draw(model, &mut shader)
+    }
+    pub fn clear(&mut self) {
+        self.canvas.clear_zbuffer();
+    }
+    /** World point in shadow map screen space */
+    pub fn to_screen(&self, point: Vector3D) -> Vector3D {
+        (self.canvas.get_viewport() * self.clip_transform).transform_point(point)
+    }
+    /** Lit part of the point given in shadow map screen space, from 0 in full shadow to 1 */
+    pub fn visibility(&self, screen: Vector3D) -> f64 {
+        let (x, y) = (screen.x.floor() as i32, screen.y.floor() as i32);
+        let mut lit = 0;
+        for d_y in -self.pcf_radius..=self.pcf_radius {
+            for d_x in -self.pcf_radius..=self.pcf_radius {
+                // nothing was drawn outside of the map, so the point is lit
+                match self.canvas.get_depth().get(x + d_x, y + d_y) {
+                    Some(depth) if screen.z + self.bias < depth => {},
+                    _ => lit += 1,
+                }
+            }
+        }
+        lit as f64 / ((2 * self.pcf_radius + 1) * (2 * self.pcf_radius + 1)) as f64
+    }
+    /** Shadowed light at the point given in shadow map screen space, weakened by `strength` */
+    pub fn light_visibility(&self, screen: Vector3D) -> Visibility {
+        Visibility {
+            light: self.light,
+            lit: 1.0 - self.strength * (1.0 - self.visibility(screen)),
+        }
+    }
+    /** Shadowed light where it is blocked completely */
+    pub fn blocked(&self) -> Visibility {
+        Visibility {
+            light: self.light,
+            lit: 1.0 - self.strength,
+        }
+    }
+}
+
+/** Depth only pass from the light */
+struct DepthShader {
+    clip_transform: Matrix4,
+}
+
+impl Shader for DepthShader {
+    type Varying = ();
+    fn vertex(&mut self, model: &Model, face: usize, nth: usize) -> (Vector4D, ()) {
+        (self.clip_transform * Vector4D::from_point(model.triangle(face).vertex(nth)), ())
+    }
+    fn fragment(&mut self, _varying: ()) -> Option<RgbColor> {
+        Some(RgbColor::new(Ergbcolor::BLACK))
+    }
+}
+
+#[test]
+fn shadow_visibility() {
+    use std::path::Path;
+    use crate::model_obj::ObjModel;
+    let model: ObjModel = Model::new(Path::new("obj/african_head.obj"));
+    let lights = [Light::directional(Vector3D {x: 0.0, y: -1.0, z: 0.0})];
+    let mut shadow = ShadowMap::new(256, &lights, 0, Vector3D::default(), 3.0).unwrap();
+    shadow.render(&model, &Matrix4::translation(Vector3D {x: 0.0, y: 1.0, z: 0.0})).unwrap();
+    let below = shadow.to_screen(Vector3D {x: 0.0, y: -1.0, z: 0.0});
+    let aside = shadow.to_screen(Vector3D {x: 1.8, y: -1.0, z: 0.0});
+    let above = shadow.to_screen(Vector3D {x: 0.0, y: 2.5, z: 0.0});
+    assert_eq!(shadow.visibility(below), 0.0);
+    assert_eq!(shadow.visibility(aside), 1.0);
+    assert_eq!(shadow.visibility(above), 1.0);
+
+    // the same caster away from the origin, with the map centred on it
+    let center = Vector3D {x: 10.0, y: 0.0, z: -5.0};
+    let mut shadow = ShadowMap::new(256, &lights, 0, center, 3.0).unwrap();
+    shadow.render(&model, &Matrix4::translation(center + Vector3D {x: 0.0, y: 1.0, z: 0.0})).unwrap();
+    assert_eq!(shadow.visibility(shadow.to_screen(center + Vector3D {x: 0.0, y: -1.0, z: 0.0})), 0.0);
+    assert_eq!(shadow.visibility(shadow.to_screen(center + Vector3D {x: 1.8, y: -1.0, z: 0.0})), 1.0);
+
+    assert!(ShadowMap::new(16, &[Light::point(Vector3D::default())], 0, Vector3D::default(), 1.0).is_err());
+    assert!(ShadowMap::new(16, &[], 0, Vector3D::default(), 1.0).is_err());
+    assert!(ShadowMap::new(0, &lights, 0, Vector3D::default(), 1.0).is_err());
+    assert!(ShadowMap::new(16, &lights, 0, Vector3D::default(), 0.0).is_err());
+    assert!(ShadowMap::new(16, &lights, 0, Vector3D::default(), -1.0).is_err());
+}
+
+#[test]
+fn shadowed_light() {
+    use crate::camera::Camera;
+    use crate::material::Material;
+    use crate::shader::Uniforms;
+    // the shadowed key light from above and a fill light from the front
+    let lights = [
+        Light::directional(Vector3D {x: 0.0, y: -1.0, z: 0.0}),
+        Light::directional(Vector3D {x: 0.0, y: 0.0, z: -1.0}).with_intensity(0.5),
+    ];
+    let camera = Camera::perspective(Vector3D {x: 0.0, y: 0.0, z: 3.0}, Vector3D::default());
+    let uniforms = Uniforms::new(&Matrix4::identity(), &camera, &camera.projection_matrix(1.0), &lights);
+    let point = camera.view_matrix().transform_point(Vector3D::default());
+    let normal = camera.view_matrix().transform_direction(Vector3D {x: 0.0, y: 1.0, z: 1.0}.normalize());
+    let material = Material::default();
+    let lit = uniforms.shade(point, normal, &material, None).0;
+    let key_blocked = uniforms.shade(point, normal, &material, Some(Visibility { light: 0, lit: 0.0 })).0;
+    let fill_blocked = uniforms.shade(point, normal, &material, Some(Visibility { light: 1, lit: 0.0 })).0;
+    // ambient and the fill light still reach the point in the shadow of the key light
+    let fill = 0.5 * material.diffuse * std::f64::consts::FRAC_1_SQRT_2;
+    assert!((key_blocked.x - (material.ambient + fill)).abs() < 1e-9);
+    assert!(fill_blocked.x > key_blocked.x && lit.x > fill_blocked.x);
+}
@@ -0,0 +1,101 @@
+use std::path::Path;
+use image::RgbImage;
+
+use crate::tga;
+use crate::canvas_trait::RgbColor;
+use crate::geometry::Vector3D;
+
+/** Image sampled by texture coordinates, (0, 0) is the bottom left corner */
+pub struct Texture {
+    image: RgbImage,
+}
+
+impl Texture {
+    /** TGA files are read by the native decoder, other formats by the image crate */
+    pub fn load(file_path: &Path) -> Result<Texture, String> {
+        let is_tga = file_path.extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("tga"));
+        if is_tga {
+            let (width, height, pixels) = tga::load(file_path)?;
+            return Ok(Texture::from_pixels(width, height, &pixels));
+        }
+        let image = image::open(file_path).map_err(|e| e.to_string())?.to_rgb();
+        if image.width() == 0 || image.height() == 0 {
+            return Err(format!("Image is empty, {}x{}", image.width(), image.height()));
+        }
+        Ok(Texture::from_image(image))
+    }
+    pub fn from_image(image: RgbImage) -> Texture {
+        Texture {
+            image: image,
+        }
+    }
+    /** 0xAARRGGBB pixels top row first, alpha is dropped */
+    pub fn from_pixels(width: u32, height: u32, pixels: &[u32]) -> Texture {
+        Texture::from_image(RgbImage::from_fn(width, height, |x, y| {
+            let pixel = pixels[(y * width + x) as usize];
+            image::Rgb { data: [(pixel >> (8*2)) as u8, (pixel >> 8) as u8, pixel as u8] }
+        }))
+    }
+    pub fn get_width(&self) -> u32 {
+        self.image.width()
+    }
+    pub fn get_height(&self) -> u32 {
+        self.image.height()
+    }
+    /** Nearest texel at the uv point, coordinates are clamped to [0, 1] */
+    fn texel(&self, uv: Vector3D) -> [u8; 3] {
+        let u = uv.x.max(0.0).min(1.0);
+        let v = uv.y.max(0.0).min(1.0);
+        let x = ((u * self.get_width() as f64) as u32).min(self.get_width() - 1);
+        let y = (((1.0 - v) * self.get_height() as f64) as u32).min(self.get_height() - 1);
+        self.image.get_pixel(x, y).data
+    }
+    pub fn sample(&self, uv: Vector3D) -> RgbColor {
+        let texel = self.texel(uv);
+        RgbColor::from_rgb(texel[0], texel[1], texel[2])
+    }
+    /** Normal map texel decoded from [0, 255] to a unit vector with components in [-1, 1] */
+    pub fn sample_normal(&self, uv: Vector3D) -> Vector3D {
+        let texel = self.texel(uv);
+        Vector3D {
+            x: texel[0] as f64 / 255.0 * 2.0 - 1.0,
+            y: texel[1] as f64 / 255.0 * 2.0 - 1.0,
+            z: texel[2] as f64 / 255.0 * 2.0 - 1.0,
+        }.normalize()
+    }
+}
+
+#[test]
+fn texture_sample() {
+    let texture = Texture::load(Path::new("obj/jackal.jpg")).unwrap();
+    println!("Texture {}x{}", texture.get_width(), texture.get_height());
+    assert_eq!((texture.get_width(), texture.get_height()), (2089, 2633));
+    // v goes up, so (0, 0) is the bottom left pixel of the image
+    let corners = [(0.0, 0.0, 0xFFBFC0A0), (1.0, 0.0, 0xFF727E68), (0.0, 1.0, 0xFFE1D6BA), (1.0, 1.0, 0xFFDFD8AC)];
+    for &(u, v, texel) in corners.iter() {
+        assert_eq!(texture.sample(Vector3D {x: u, y: v, z: 0.0}).value(), texel, "texel at {},{}", u, v);
+    }
+    // coordinates outside of the image are clamped to its edges
+    assert_eq!(texture.sample(Vector3D {x: -1.0, y: 2.0, z: 0.0}).value(), 0xFFE1D6BA);
+}
+
+#[test]
+fn texture_sample_normal() {
+    let flat = Texture::from_image(RgbImage::from_pixel(2, 2, image::Rgb { data: [128, 128, 255] }));
+    let normal = flat.sample_normal(Vector3D {x: 0.5, y: 0.5, z: 0.0});
+    assert!((normal.z - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn texture_load_tga() {
+    let pixels = [0xFFFF0000, 0xFF00FF00, 0xFF0000FF, 0xFFFFFFFF];
+    let bytes = tga::encode(2, 2, &pixels, false, true, tga::Origin::BottomLeft).unwrap();
+    let (width, height, decoded) = tga::decode(&bytes).unwrap();
+    let texture = Texture::from_pixels(width, height, &decoded);
+    // v goes up, so the first pixel row is at the top
+    assert_eq!(texture.sample(Vector3D {x: 0.25, y: 0.75, z: 0.0}).value(), 0xFFFF0000);
+    assert_eq!(texture.sample(Vector3D {x: 0.75, y: 0.25, z: 0.0}).value(), 0xFFFFFFFF);
+    assert!(Texture::load(Path::new("obj/missing.tga")).is_err());
+}
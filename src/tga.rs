@@ -0,0 +1,248 @@
+use std::path::Path;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+
+const HEADER_SIZE: usize = 18;
+const IMAGE_TRUECOLOR: u8 = 2;
+const IMAGE_GRAYSCALE: u8 = 3;
+const IMAGE_TRUECOLOR_RLE: u8 = 10;
+const IMAGE_GRAYSCALE_RLE: u8 = 11;
+/** Image descriptor bit of columns stored right to left */
+const DESCRIPTOR_RIGHT: u8 = 0x10;
+/** Image descriptor bit of rows stored top to bottom */
+const DESCRIPTOR_TOP: u8 = 0x20;
+/** Longest packet, the count is stored in 7 bits minus one */
+const MAX_PACKET: usize = 128;
+const FOOTER_SIGNATURE: &[u8] = b"TRUEVISION-XFILE.\0";
+
+/** Corner of the image where the first stored row begins */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Origin {
+    BottomLeft,
+    TopLeft,
+}
+
+/**
+ * TGA file bytes of 0xAARRGGBB `pixels` given top row first.
+ * `alpha` selects 32 bit BGRA pixels instead of 24 bit BGR, `rle` run-length encodes them.
+ */
+pub fn encode(width: u32, height: u32, pixels: &[u32], alpha: bool, rle: bool, origin: Origin) -> Result<Vec<u8>, String> {
+    if width == 0 || height == 0 || width > 0xFFFF || height > 0xFFFF {
+        return Err(format!("TGA can't store {}x{} image", width, height));
+    }
+    if pixels.len() != (width * height) as usize {
+        return Err("Pixel count doesn't match image size".to_string());
+    }
+    let depth = if alpha { 4 } else { 3 };
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + pixels.len() * depth);
+    bytes.push(0); // no image id
+    bytes.push(0); // no color map
+    bytes.push(if rle { IMAGE_TRUECOLOR_RLE } else { IMAGE_TRUECOLOR });
+    bytes.extend_from_slice(&[0; 5]); // color map specification
+    bytes.extend_from_slice(&[0; 4]); // x and y origin
+    bytes.extend_from_slice(&(width as u16).to_le_bytes());
+    bytes.extend_from_slice(&(height as u16).to_le_bytes());
+    bytes.push(depth as u8 * 8);
+    let alpha_bits = if alpha { 8 } else { 0 };
+    bytes.push(alpha_bits | if origin == Origin::TopLeft { DESCRIPTOR_TOP } else { 0 });
+    let rows: Vec<&[u32]> = match origin {
+        Origin::TopLeft => pixels.chunks(width as usize).collect(),
+        Origin::BottomLeft => pixels.chunks(width as usize).rev().collect(),
+    };
+    for row in rows {
+        let pixel = |bytes: &mut Vec<u8>, color: u32| {
+            bytes.extend_from_slice(&[color as u8, (color >> 8) as u8, (color >> (8*2)) as u8]);
+            if alpha {
+                bytes.push((color >> (8*3)) as u8);
+            }
+        };
+        // colors that aren't saved must not break runs
+        let key = |color: u32| if alpha { color } else { color & 0xFFFFFF };
+        if !rle {
+            for &color in row {
+                pixel(&mut bytes, color);
+            }
+            continue;
+        }
+        // packets don't cross rows, as the specification recommends
+        let mut i = 0;
+        while i < row.len() {
+            let run = row[i..].iter().take(MAX_PACKET).take_while(|&&color| key(color) == key(row[i])).count();
+            if run > 1 {
+                bytes.push(0x80 | (run - 1) as u8);
+                pixel(&mut bytes, row[i]);
+                i += run;
+                continue;
+            }
+            // raw packet lasts until the next run of two
+            let mut end = i + 1;
+            while end < row.len() && end - i < MAX_PACKET
+                  && !(end + 1 < row.len() && key(row[end]) == key(row[end + 1])) {
+                end += 1;
+            }
+            bytes.push((end - i - 1) as u8);
+            for &color in row[i..end].iter() {
+                pixel(&mut bytes, color);
+            }
+            i = end;
+        }
+    }
+    // TGA 2.0 footer without extension and developer areas
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(FOOTER_SIGNATURE);
+    Ok(bytes)
+}
+
+pub fn save(path: &Path, width: u32, height: u32, pixels: &[u32], alpha: bool, rle: bool, origin: Origin) -> Result<(), String> {
+    let bytes = encode(width, height, pixels, alpha, rle, origin)?;
+    let file = File::create(path).map_err(|err| format!("Can't create {}: {}", path.display(), err))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&bytes)
+        .and_then(|_| writer.flush())
+        .map_err(|err| format!("Can't write {}: {}", path.display(), err))
+}
+
+/** Width, height and 0xAARRGGBB pixels top row first of TGA file bytes, truecolor or grayscale */
+pub fn decode(bytes: &[u8]) -> Result<(u32, u32, Vec<u32>), String> {
+    if bytes.len() < HEADER_SIZE {
+        return Err("TGA header is truncated".to_string());
+    }
+    let image_type = bytes[2];
+    let width = u16::from_le_bytes([bytes[12], bytes[13]]) as usize;
+    let height = u16::from_le_bytes([bytes[14], bytes[15]]) as usize;
+    let depth = bytes[16] as usize / 8;
+    let descriptor = bytes[17];
+    if width == 0 || height == 0 {
+        return Err(format!("TGA image is empty, {}x{}", width, height));
+    }
+    let grayscale = match (image_type, depth) {
+        (IMAGE_TRUECOLOR, 3) | (IMAGE_TRUECOLOR, 4) | (IMAGE_TRUECOLOR_RLE, 3) | (IMAGE_TRUECOLOR_RLE, 4) => false,
+        (IMAGE_GRAYSCALE, 1) | (IMAGE_GRAYSCALE_RLE, 1) => true,
+        _ => return Err(format!("Unsupported TGA image type {} with {} bits per pixel", image_type, bytes[16])),
+    };
+    // truecolor and grayscale images may still carry a color map, it is skipped
+    let color_map = match bytes[1] {
+        0 => 0,
+        1 => u16::from_le_bytes([bytes[5], bytes[6]]) as usize * ((bytes[7] as usize + 7) / 8),
+        other => return Err(format!("Unknown TGA color map type {}", other)),
+    };
+    let color = |pixel: &[u8]| -> u32 {
+        if grayscale {
+            let value = pixel[0] as u32;
+            0xFF000000 | value << (8*2) | value << 8 | value
+        } else {
+            let alpha = if depth == 4 { pixel[3] as u32 } else { 0xFF };
+            alpha << (8*3) | (pixel[2] as u32) << (8*2) | (pixel[1] as u32) << 8 | pixel[0] as u32
+        }
+    };
+    let truncated = || "TGA pixel data is truncated".to_string();
+    // pixels in stored order, the image id and color map are skipped
+    let mut offset = HEADER_SIZE + bytes[0] as usize + color_map;
+    let mut stored = Vec::with_capacity(width * height);
+    while stored.len() < width * height {
+        if image_type == IMAGE_TRUECOLOR || image_type == IMAGE_GRAYSCALE {
+            let pixel = bytes.get(offset..offset + depth).ok_or_else(truncated)?;
+            stored.push(color(pixel));
+            offset += depth;
+            continue;
+        }
+        let packet = *bytes.get(offset).ok_or_else(truncated)?;
+        let count = (packet & 0x7F) as usize + 1;
+        offset += 1;
+        if packet & 0x80 != 0 {
+            let pixel = bytes.get(offset..offset + depth).ok_or_else(truncated)?;
+            stored.extend(std::iter::repeat(color(pixel)).take(count));
+            offset += depth;
+        } else {
+            let pixels = bytes.get(offset..offset + count * depth).ok_or_else(truncated)?;
+            stored.extend(pixels.chunks(depth).map(|pixel| color(pixel)));
+            offset += count * depth;
+        }
+    }
+    // a packet may run past the last row of a broken file
+    stored.truncate(width * height);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = if descriptor & DESCRIPTOR_TOP != 0 { y } else { height - 1 - y };
+        let row = &stored[row * width..(row + 1) * width];
+        if descriptor & DESCRIPTOR_RIGHT != 0 {
+            pixels.extend(row.iter().rev());
+        } else {
+            pixels.extend_from_slice(row);
+        }
+    }
+    Ok((width as u32, height as u32, pixels))
+}
+
+pub fn load(path: &Path) -> Result<(u32, u32, Vec<u32>), String> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+    decode(&bytes)
+}
+
+#[test]
+fn tga_encoding() {
+    let pixels = [0xFF112233, 0xFF112233, 0xFF112233, 0x00445566,
+                  0xFFAABBCC, 0xFFDDEEFF, 0xFF000000, 0xFF000000];
+    let raw = encode(4, 2, &pixels, false, false, Origin::BottomLeft).unwrap();
+    assert_eq!(&raw[..HEADER_SIZE], &[0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 2, 0, 24, 0]);
+    // bottom row goes first
+    assert_eq!(&raw[HEADER_SIZE..HEADER_SIZE + 6], &[0xCC, 0xBB, 0xAA, 0xFF, 0xEE, 0xDD]);
+    assert_eq!(raw.len(), HEADER_SIZE + 8 * 3 + 26);
+    assert!(raw.ends_with(FOOTER_SIGNATURE));
+
+    let rle = encode(4, 2, &pixels, true, true, Origin::TopLeft).unwrap();
+    assert_eq!(rle[2], IMAGE_TRUECOLOR_RLE);
+    assert_eq!(&rle[16..HEADER_SIZE], &[32, 8 | DESCRIPTOR_TOP]);
+    let data = &rle[HEADER_SIZE..rle.len() - 26];
+    assert_eq!(data, &[0x82, 0x33, 0x22, 0x11, 0xFF,
+                       0x00, 0x66, 0x55, 0x44, 0x00,
+                       0x01, 0xCC, 0xBB, 0xAA, 0xFF, 0xFF, 0xEE, 0xDD, 0xFF,
+                       0x81, 0x00, 0x00, 0x00, 0xFF][..]);
+
+    // long uniform rows are split into packets of 128
+    let wide = vec![0xFF808080; 300];
+    let rle = encode(300, 1, &wide, false, true, Origin::BottomLeft).unwrap();
+    assert_eq!(rle.len(), HEADER_SIZE + 3 * 4 + 26);
+    assert_eq!(rle[HEADER_SIZE], 0xFF);
+    assert!(encode(0, 1, &[], false, true, Origin::BottomLeft).is_err());
+}
+
+#[test]
+fn tga_decoding() {
+    let pixels = [0xFF112233, 0xFF112233, 0xFF112233, 0x00445566,
+                  0xFFAABBCC, 0xFFDDEEFF, 0xFF000000, 0xFF000000];
+    for &rle in [false, true].iter() {
+        for &origin in [Origin::BottomLeft, Origin::TopLeft].iter() {
+            let bytes = encode(4, 2, &pixels, true, rle, origin).unwrap();
+            assert_eq!(decode(&bytes), Ok((4, 2, pixels.to_vec())));
+            let bytes = encode(4, 2, &pixels, false, rle, origin).unwrap();
+            assert_eq!(decode(&bytes).unwrap().2[3], 0xFF445566);
+        }
+    }
+    // RLE grayscale, bottom-left origin, right to left, with an image id
+    let mut gray = vec![1, 0, IMAGE_GRAYSCALE_RLE, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 2, 0, 8, DESCRIPTOR_RIGHT, 0xEE];
+    gray.extend_from_slice(&[0x82, 0x10, 0x01, 0x20, 0x30, 0x00, 0x40]);
+    assert_eq!(decode(&gray), Ok((3, 2, vec![0xFF404040, 0xFF303030, 0xFF202020,
+                                              0xFF101010, 0xFF101010, 0xFF101010])));
+    assert!(decode(&gray[..gray.len() - 1]).is_err());
+    gray[2] = 1;
+    assert!(decode(&gray).is_err());
+
+    // truecolor with an unused color map of two 24 bit entries
+    let mut mapped = encode(4, 2, &pixels, true, false, Origin::TopLeft).unwrap();
+    mapped[1] = 1;
+    mapped[5] = 2;
+    mapped[7] = 24;
+    mapped.splice(HEADER_SIZE..HEADER_SIZE, vec![0xAB; 6]);
+    assert_eq!(decode(&mapped), Ok((4, 2, pixels.to_vec())));
+    mapped[1] = 2;
+    assert!(decode(&mapped).is_err());
+
+    // a header without pixels is no image
+    let mut empty = encode(4, 2, &pixels, false, false, Origin::TopLeft).unwrap();
+    empty[12] = 0;
+    assert_eq!(decode(&empty), Err("TGA image is empty, 0x2".to_string()));
+}
@@ -0,0 +1,51 @@
+/** Per-canvas depth buffer, bigger z is closer to the viewer */
+pub struct ZBuffer {
+    width: u32,
+    height: u32,
+    depth: Vec<f64>,
+}
+
+impl ZBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        ZBuffer {
+            width: width,
+            height: height,
+            depth: vec![std::f64::NEG_INFINITY; (width * height) as usize],
+        }
+    }
+    pub fn clear(&mut self) {
+        for z in self.depth.iter_mut() {
+            *z = std::f64::NEG_INFINITY;
+        }
+    }
+    pub fn get(&self, x: i32, y: i32) -> Option<f64> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return None;
+        }
+        Some(self.depth[(y as u32 * self.width + x as u32) as usize])
+    }
+    /** Check if the point is closer than the one already drawn */
+    pub fn test(&self, x: i32, y: i32, z: f64) -> bool {
+        match self.get(x, y) {
+            Some(current) => z > current,
+            None => false,
+        }
+    }
+    pub fn set(&mut self, x: i32, y: i32, z: f64) {
+        if self.get(x, y).is_some() {
+            self.depth[(y as u32 * self.width + x as u32) as usize] = z;
+        }
+    }
+}
+
+#[test]
+fn zbuffer_depth_test() {
+    let mut zbuffer = ZBuffer::new(10, 10);
+    assert!(zbuffer.test(1, 1, -5.0));
+    zbuffer.set(1, 1, -5.0);
+    assert!(!zbuffer.test(1, 1, -6.0));
+    assert!(zbuffer.test(1, 1, 3.0));
+    assert!(!zbuffer.test(10, 1, 3.0));
+    zbuffer.clear();
+    assert!(zbuffer.test(1, 1, -6.0));
+}